@@ -0,0 +1,21 @@
+#![no_main]
+
+use bank_ocr::checksum::AccountFormat;
+use bank_ocr::parser::{parse_entries, parse_entries_normalized};
+use bank_ocr::stream::Reports;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let format = AccountFormat::kata();
+    for entry in parse_entries(data) {
+        if entry.is_err() {
+            break;
+        }
+    }
+    let entries = parse_entries_normalized(data, format.width());
+    for report in Reports::new(entries, &format) {
+        if report.is_err() {
+            break;
+        }
+    }
+});
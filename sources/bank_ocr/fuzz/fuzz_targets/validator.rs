@@ -0,0 +1,12 @@
+#![no_main]
+
+use bank_ocr::validator::{is_valid_account_number, solve_missing_digit};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|account_number: &str| {
+    let valid = is_valid_account_number(account_number);
+    if valid {
+        assert!(account_number.chars().all(|c| c.is_ascii_digit()));
+    }
+    let _ = solve_missing_digit(account_number);
+});
@@ -0,0 +1,452 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::checksum::AccountFormat;
+use crate::recognizer::{cat_number, recognize_digit};
+
+/// The number of digits of an account number in the kata.
+pub const ACCOUNT_NUMBER_LENGTH: usize = 9;
+
+pub type Glyph = [[char; 3]; 3];
+
+/// One digit slot of an account number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Digit {
+    Known(u8),
+    Illegible,
+    /// A character of the glyph table that is not a digit, e.g. the `-` of
+    /// `GlyphTable::dash`. No checksum is valid with it.
+    Symbol(char),
+}
+
+impl Digit {
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '0'..='9' => Some(Digit::Known(c as u8 - b'0')),
+            '?' => Some(Digit::Illegible),
+            _ => None,
+        }
+    }
+
+    /// The slot of a character recognized by a `GlyphTable`.
+    pub fn recognized(c: char) -> Self {
+        Digit::from_char(c).unwrap_or(Digit::Symbol(c))
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Digit::Known(d) => (b'0' + d) as char,
+            Digit::Illegible => '?',
+            Digit::Symbol(c) => c,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    Ok,
+    Err,
+    Ill,
+    Amb,
+}
+
+impl Status {
+    /// Returns the suffix used in the report, or `None` for a valid number.
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            Status::Ok => None,
+            Status::Err => Some("ERR"),
+            Status::Ill => Some("ILL"),
+            Status::Amb => Some("AMB"),
+        }
+    }
+}
+
+/// An account number of as many digit slots as `AccountFormat::length`.
+///
+/// `checksum`, `is_valid` and `status` use the kata's mod 11 scheme; the
+/// `_in` methods use the scheme and length of a format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountNumber {
+    digits: Vec<Digit>,
+    glyphs: Option<Vec<Glyph>>,
+    alternatives: Vec<AccountNumber>,
+}
+
+impl AccountNumber {
+    pub fn new(digits: Vec<Digit>) -> Self {
+        Self {
+            digits,
+            glyphs: None,
+            alternatives: Vec::new(),
+        }
+    }
+
+    /// Recognizes the account number of a scanned kata entry and keeps its glyphs.
+    pub fn from_pattern(pattern: &str) -> Self {
+        let glyphs: Vec<Glyph> = (0..ACCOUNT_NUMBER_LENGTH)
+            .map(|i| cat_number(pattern, i))
+            .collect();
+        let digits = glyphs
+            .iter()
+            .map(|glyph| Digit::recognized(recognize_digit(*glyph)))
+            .collect();
+        Self {
+            digits,
+            glyphs: Some(glyphs),
+            alternatives: Vec::new(),
+        }
+    }
+
+    /// Recognizes the `format.length` glyphs of an entry drawn in
+    /// `format.layout` with `format.table`, as `recognizer::recognize_entry`,
+    /// and keeps the glyphs as cut by the layout.
+    pub fn recognize(pattern: &str, format: &AccountFormat) -> Self {
+        let glyphs: Vec<Glyph> = (0..format.length)
+            .map(|i| format.layout.cut(pattern, i))
+            .collect();
+        let digits = glyphs
+            .iter()
+            .map(|glyph| Digit::recognized(format.table.recognize(glyph)))
+            .collect();
+        Self {
+            digits,
+            glyphs: Some(glyphs),
+            alternatives: Vec::new(),
+        }
+    }
+
+    /// Parses a line of the report of `format`, see `FromStr`, which parses
+    /// the kata's.
+    pub fn parse_in(s: &str, format: &AccountFormat) -> Result<Self, ParseAccountNumberError> {
+        let (number, status) = match s.split_once(' ') {
+            Some((number, status)) => (number, Some(status.trim())),
+            None => (s, None),
+        };
+        let length = number.chars().count();
+        if length != format.length {
+            return Err(ParseAccountNumberError::InvalidLength(length));
+        }
+        let digits = number
+            .chars()
+            .map(|c| Digit::from_char(c).ok_or(ParseAccountNumberError::InvalidDigit(c)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut account_number = AccountNumber::new(digits);
+        if let Some(alternatives) = status.and_then(|status| status.strip_prefix("AMB")) {
+            let alternatives = parse_alternatives(alternatives, format)?;
+            account_number = account_number.with_alternatives(alternatives);
+        } else if let Some(status) = status {
+            let expected = account_number.status_in(format);
+            if expected.label() != Some(status) {
+                return Err(ParseAccountNumberError::StatusMismatch {
+                    expected,
+                    found: status.to_string(),
+                });
+            }
+        }
+        Ok(account_number)
+    }
+
+    /// Marks the number as ambiguous between the valid `alternatives`.
+    pub fn with_alternatives(mut self, alternatives: Vec<AccountNumber>) -> Self {
+        self.alternatives = alternatives;
+        self
+    }
+
+    pub fn alternatives(&self) -> &[AccountNumber] {
+        &self.alternatives
+    }
+
+    pub fn digits(&self) -> &[Digit] {
+        &self.digits
+    }
+
+    /// Returns the scanned glyphs, if the number was recognized from a scan.
+    pub fn glyphs(&self) -> Option<&[Glyph]> {
+        self.glyphs.as_deref()
+    }
+
+    /// The positions of the illegible digits.
+    pub fn illegible_positions(&self) -> Vec<usize> {
+        self.digits
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| **d == Digit::Illegible)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn is_legible(&self) -> bool {
+        self.digits.iter().all(|d| *d != Digit::Illegible)
+    }
+
+    /// The values of the digits, or `None` unless every slot is a known digit.
+    fn known_digits(&self) -> Option<Vec<u8>> {
+        self.digits
+            .iter()
+            .map(|d| match d {
+                Digit::Known(d) => Some(*d),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns `(d1 + 2*d2 + ... + 9*d9) mod 11`, or `None` unless every slot
+    /// is a known digit.
+    pub fn checksum(&self) -> Option<u32> {
+        let digits = self.known_digits()?;
+        let check_sum: u32 = digits
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, d)| *d as u32 * (i as u32 + 1))
+            .sum();
+        Some(check_sum % 11)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.checksum() == Some(0)
+    }
+
+    /// Whether the number has `format.length` digits and `format.checksum` holds.
+    pub fn is_valid_in(&self, format: &AccountFormat) -> bool {
+        self.known_digits().is_some_and(|digits| {
+            digits.len() == format.length && format.checksum.is_valid(&digits)
+        })
+    }
+
+    pub fn status(&self) -> Status {
+        self.status_by(self.is_valid())
+    }
+
+    pub fn status_in(&self, format: &AccountFormat) -> Status {
+        self.status_by(self.is_valid_in(format))
+    }
+
+    fn status_by(&self, valid: bool) -> Status {
+        if !self.alternatives.is_empty() {
+            Status::Amb
+        } else if !self.is_legible() {
+            Status::Ill
+        } else if valid {
+            Status::Ok
+        } else {
+            Status::Err
+        }
+    }
+
+    /// Returns the digits without the status, e.g. `86110??36`.
+    pub fn number(&self) -> String {
+        self.digits.iter().map(|d| d.to_char()).collect()
+    }
+}
+
+impl fmt::Display for AccountNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number())?;
+        if let Some(label) = self.status().label() {
+            write!(f, " {label}")?;
+        }
+        if !self.alternatives.is_empty() {
+            let alternatives: Vec<String> = self
+                .alternatives
+                .iter()
+                .map(|a| format!("'{}'", a.number()))
+                .collect();
+            write!(f, " [{}]", alternatives.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseAccountNumberError {
+    InvalidLength(usize),
+    InvalidDigit(char),
+    StatusMismatch { expected: Status, found: String },
+    InvalidAlternatives(String),
+}
+
+impl fmt::Display for ParseAccountNumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseAccountNumberError::InvalidLength(length) => {
+                write!(f, "invalid number of digits: {length}")
+            }
+            ParseAccountNumberError::InvalidDigit(c) => write!(f, "invalid digit: {c:?}"),
+            ParseAccountNumberError::StatusMismatch { expected, found } => {
+                write!(f, "expected status {expected:?} but found {found:?}")
+            }
+            ParseAccountNumberError::InvalidAlternatives(alternatives) => {
+                write!(f, "invalid alternatives: {alternatives:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseAccountNumberError {}
+
+impl FromStr for AccountNumber {
+    type Err = ParseAccountNumberError;
+
+    /// Parses one line of the kata's report, e.g. `664371495 ERR` or
+    /// `555555555 AMB ['555655555', '559555555']`. The status is optional,
+    /// but if present it must agree with the checksum.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AccountNumber::parse_in(s, &AccountFormat::kata())
+    }
+}
+
+/// Parses the `['555655555', '559555555']` list of an AMB line. Every
+/// alternative must be a valid number of `format`.
+fn parse_alternatives(
+    s: &str,
+    format: &AccountFormat,
+) -> Result<Vec<AccountNumber>, ParseAccountNumberError> {
+    let invalid = || ParseAccountNumberError::InvalidAlternatives(s.to_string());
+    let list = s
+        .trim()
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let alternatives = list
+        .split(',')
+        .map(|a| {
+            let a = a.trim().strip_prefix('\'')?.strip_suffix('\'')?;
+            AccountNumber::parse_in(a, format)
+                .ok()
+                .filter(|a| a.is_valid_in(format))
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+    Ok(alternatives)
+}
+
+#[cfg(test)]
+mod tests_account_number {
+    use super::*;
+
+    #[test]
+    fn test_from_pattern() {
+        let pattern = "    _  _     _  _  _  _  _ \n  | _| _||_| _ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _ \n                           ";
+        let account_number = AccountNumber::from_pattern(pattern);
+        assert_eq!("1234?678?", account_number.number());
+        assert_eq!(Digit::Known(1), account_number.digits()[0]);
+        assert_eq!(Digit::Illegible, account_number.digits()[4]);
+        assert_eq!(
+            crate::recognizer::TWO_PATTERN,
+            account_number.glyphs().unwrap()[1]
+        );
+    }
+
+    #[test]
+    fn test_checksum_and_status() {
+        let valid: AccountNumber = "345882865".parse().unwrap();
+        assert_eq!(Some(0), valid.checksum());
+        assert!(valid.is_valid());
+        assert_eq!(Status::Ok, valid.status());
+
+        let invalid: AccountNumber = "664371495".parse().unwrap();
+        assert!(invalid.is_legible());
+        assert_eq!(Status::Err, invalid.status());
+
+        let illegible: AccountNumber = "86110??36".parse().unwrap();
+        assert!(!illegible.is_legible());
+        assert_eq!(None, illegible.checksum());
+        assert_eq!(Status::Ill, illegible.status());
+    }
+
+    #[test]
+    fn test_round_trip_report_format() {
+        for line in [
+            "457508000",
+            "664371495 ERR",
+            "86110??36 ILL",
+            "555555555 AMB ['555655555', '559555555']",
+        ] {
+            let account_number: AccountNumber = line.parse().unwrap();
+            assert_eq!(line, account_number.to_string());
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(
+            Err(ParseAccountNumberError::InvalidLength(8)),
+            "45750800".parse::<AccountNumber>()
+        );
+        assert_eq!(
+            Err(ParseAccountNumberError::InvalidDigit('x')),
+            "4575080x0".parse::<AccountNumber>()
+        );
+        assert_eq!(
+            Err(ParseAccountNumberError::StatusMismatch {
+                expected: Status::Ok,
+                found: "ERR".to_string()
+            }),
+            "457508000 ERR".parse::<AccountNumber>()
+        );
+        for line in [
+            "555555555 AMB",
+            "555555555 AMB []",
+            "555555555 AMB ['555555555']",
+            "555555555 AMB [555655555]",
+        ] {
+            assert!(matches!(
+                line.parse::<AccountNumber>(),
+                Err(ParseAccountNumberError::InvalidAlternatives(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_ambiguous() {
+        let ambiguous: AccountNumber = "555555555 AMB ['555655555', '559555555']".parse().unwrap();
+        assert_eq!(Status::Amb, ambiguous.status());
+        assert_eq!("555555555", ambiguous.number());
+        assert_eq!("559555555", ambiguous.alternatives()[1].number());
+        assert!(!ambiguous.is_valid());
+    }
+
+    #[test]
+    fn test_length_of_format() {
+        use crate::checksum::Verhoeff;
+        use crate::glyph_table::GlyphTable;
+        use crate::renderer::render_with_glyphs;
+
+        let format = AccountFormat::new(4, Box::new(Verhoeff));
+        let valid = AccountNumber::parse_in("2363", &format).unwrap();
+        assert!(valid.is_valid_in(&format));
+        assert_eq!(Status::Ok, valid.status_in(&format));
+        assert_eq!(
+            Err(ParseAccountNumberError::InvalidLength(9)),
+            AccountNumber::parse_in("457508000", &format)
+        );
+        assert_eq!(
+            Err(ParseAccountNumberError::StatusMismatch {
+                expected: Status::Err,
+                found: "ILL".to_string()
+            }),
+            AccountNumber::parse_in("2364 ILL", &format)
+        );
+        assert!(AccountNumber::parse_in("2364 AMB ['2363']", &format).is_ok());
+        assert!(AccountNumber::parse_in("2364 AMB ['2364']", &format).is_err());
+
+        // 表の数字以外の文字はどのチェックサムでも無効
+        let format =
+            AccountFormat::new(5, Box::new(crate::checksum::Mod11)).with_table(GlyphTable::dash());
+        let pattern = render_with_glyphs("12-34", &GlyphTable::dash())
+            .unwrap()
+            .replacen("  |", "   ", 1);
+        let recognized = AccountNumber::recognize(&pattern, &format);
+        assert_eq!("?2-34", recognized.number());
+        assert_eq!(Digit::Symbol('-'), recognized.digits()[2]);
+        assert_eq!(vec![0], recognized.illegible_positions());
+        assert_eq!(5, recognized.glyphs().unwrap().len());
+        assert_eq!(Status::Ill, recognized.status_in(&format));
+        let dashed = AccountNumber::new("12-34".chars().map(Digit::recognized).collect());
+        assert!(dashed.is_legible());
+        assert_eq!(None, dashed.checksum());
+        assert_eq!(Status::Err, dashed.status_in(&format));
+    }
+}
@@ -0,0 +1,445 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::checksum::AccountFormat;
+use crate::output::{OutputFormat, csv_field, json_string};
+use crate::parser::{ParseError, parse_entries_normalized, parse_entries_with_width};
+use crate::registry::AccountRegistry;
+use crate::report::Summary;
+use crate::search::SearchOptions;
+use crate::stream::Reports;
+
+/// The names of the summaries written next to the reports.
+pub const SUMMARY_CSV: &str = "summary.csv";
+pub const SUMMARY_JSON: &str = "summary.json";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Number of files processed at the same time.
+    pub workers: usize,
+    /// Corrects ERR and ILL numbers when given.
+    pub search: Option<SearchOptions>,
+    /// The format of the report of each file.
+    pub output: OutputFormat,
+    /// Normalizes trimmed, drifted, tabbed or CRLF lines before recognition.
+    pub normalize: bool,
+    /// Drops alternatives that are not registered and flags unknown numbers.
+    pub registry: Option<AccountRegistry>,
+}
+
+impl Default for BatchOptions {
+    /// One worker per CPU, correcting with the default search.
+    fn default() -> Self {
+        Self {
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            search: Some(SearchOptions::default()),
+            output: OutputFormat::Text,
+            normalize: true,
+            registry: None,
+        }
+    }
+}
+
+/// The outcome of one scanner file of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileResult {
+    pub input: PathBuf,
+    pub report: PathBuf,
+    /// The entries read before `error`, if any.
+    pub summary: Summary,
+    /// Why the file could not be read to the end.
+    pub error: Option<String>,
+}
+
+impl FileResult {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Where the report of `input` is written: `<output_dir>/<file name>.report`.
+pub fn report_path(output_dir: &Path, input: &Path) -> PathBuf {
+    let name = input.file_name().unwrap_or(input.as_os_str());
+    let mut report = name.to_os_string();
+    report.push(".report");
+    output_dir.join(report)
+}
+
+/// Returns `true` for the reports and summaries a batch writes, so that a batch
+/// writing into its input directory does not read them back.
+fn is_generated(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    path.extension()
+        .is_some_and(|extension| extension == "report")
+        || name.is_some_and(|name| name == SUMMARY_CSV || name == SUMMARY_JSON)
+}
+
+/// Writes the report of one scanner file. Broken entries are counted in the
+/// summary and skipped. A file that cannot be read leaves the report of the
+/// entries before the error and the error in the result, as does a panic while
+/// processing it.
+pub fn process_file(
+    input: &Path,
+    report: &Path,
+    format: &AccountFormat,
+    options: &BatchOptions,
+) -> FileResult {
+    let mut summary = Summary::default();
+    let written = panic::catch_unwind(AssertUnwindSafe(|| {
+        write_report(input, report, format, options, &mut summary)
+    }));
+    let error = match written {
+        Ok(written) => written.err().map(|e| e.to_string()),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Some(format!("panicked: {message}"))
+        }
+    };
+    FileResult {
+        input: input.to_path_buf(),
+        report: report.to_path_buf(),
+        summary,
+        error,
+    }
+}
+
+fn write_report(
+    input: &Path,
+    report: &Path,
+    format: &AccountFormat,
+    options: &BatchOptions,
+    summary: &mut Summary,
+) -> Result<(), Box<dyn Error>> {
+    let reader = BufReader::new(File::open(input)?);
+    let mut output = BufWriter::new(File::create(report)?);
+    if let Some(header) = options.output.header() {
+        writeln!(output, "{header}")?;
+    }
+    let source = input.display().to_string();
+    let entries = if options.normalize {
+        parse_entries_normalized(reader, format.width())
+    } else {
+        parse_entries_with_width(reader, format.width())
+    }
+    .with_layout(format.layout)
+    .with_table(format.table.clone());
+    let reports = Reports::new(entries, format)
+        .with_search(options.search)
+        .with_registry(options.registry.as_ref())
+        .with_source(Some(&source));
+    for report in reports {
+        let report = match report {
+            Ok(report) => report,
+            Err(e @ ParseError::Io(_)) => return Err(e.into()),
+            // 壊れたエントリは数えるだけで、残りのエントリは書き出す
+            Err(_) => {
+                summary.broken += 1;
+                continue;
+            }
+        };
+        summary.record(report.status);
+        writeln!(output, "{}", options.output.format(&report))?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Processes `inputs` with a pool of `options.workers` threads, writing the
+/// reports into `output_dir`. Results are in the order of `inputs`.
+pub fn process_files(
+    inputs: &[PathBuf],
+    output_dir: &Path,
+    format: &AccountFormat,
+    options: &BatchOptions,
+) -> Vec<FileResult> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let workers = options.workers.clamp(1, inputs.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(i) else {
+                        break;
+                    };
+                    let report = report_path(output_dir, input);
+                    let result = process_file(input, &report, format, options);
+                    if sender.send((i, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    drop(sender);
+
+    let mut results: Vec<(usize, FileResult)> = receiver.into_iter().collect();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Processes every file directly in `input_dir`, in file name order. The output
+/// directory, and reports and summaries left by an earlier batch, are skipped.
+pub fn process_directory(
+    input_dir: &Path,
+    output_dir: &Path,
+    format: &AccountFormat,
+    options: &BatchOptions,
+) -> io::Result<Vec<FileResult>> {
+    fs::create_dir_all(output_dir)?;
+    let output_dir_path = fs::canonicalize(output_dir)?;
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(input_dir)? {
+        let path = entry?.path();
+        if path.is_file() && !is_generated(&path) && fs::canonicalize(&path)? != output_dir_path {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    Ok(process_files(&inputs, output_dir, format, options))
+}
+
+/// Writes one CSV row per file with its counts per status.
+pub fn write_summary_csv<W: Write>(writer: &mut W, results: &[FileResult]) -> io::Result<()> {
+    writeln!(
+        writer,
+        "file,report,ok,corrected,err,ill,amb,reviewed,broken,error"
+    )?;
+    for result in results {
+        let s = &result.summary;
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&result.input.display().to_string()),
+            csv_field(&result.report.display().to_string()),
+            s.ok,
+            s.corrected,
+            s.err,
+            s.ill,
+            s.amb,
+            s.reviewed,
+            s.broken,
+            csv_field(result.error.as_deref().unwrap_or(""))
+        )?;
+    }
+    Ok(())
+}
+
+fn summary_json(s: &Summary) -> String {
+    format!(
+        r#""ok":{},"corrected":{},"err":{},"ill":{},"amb":{},"reviewed":{},"broken":{}"#,
+        s.ok, s.corrected, s.err, s.ill, s.amb, s.reviewed, s.broken
+    )
+}
+
+/// Writes the counts per file and in total as one JSON object.
+pub fn write_summary_json<W: Write>(writer: &mut W, results: &[FileResult]) -> io::Result<()> {
+    let mut total = Summary::default();
+    let mut failed = 0;
+    writeln!(writer, r#"{{"files":["#)?;
+    for (i, result) in results.iter().enumerate() {
+        total.add(&result.summary);
+        let error = match &result.error {
+            Some(error) => {
+                failed += 1;
+                json_string(error)
+            }
+            None => "null".to_string(),
+        };
+        writeln!(
+            writer,
+            r#"{{"file":{},"report":{},{},"error":{}}}{}"#,
+            json_string(&result.input.display().to_string()),
+            json_string(&result.report.display().to_string()),
+            summary_json(&result.summary),
+            error,
+            if i + 1 < results.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(
+        writer,
+        r#"],"total":{{"files":{},"failed":{},{}}}}}"#,
+        results.len(),
+        failed,
+        summary_json(&total)
+    )
+}
+
+#[cfg(test)]
+mod tests_batch {
+    use super::*;
+    use crate::glyph_table::GlyphTable;
+    use crate::renderer::write_scanner_file;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bank_ocr_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_scanner(path: &Path, account_numbers: &[&str]) {
+        let mut file = File::create(path).unwrap();
+        write_scanner_file(&mut file, account_numbers, &GlyphTable::digits()).unwrap();
+    }
+
+    #[test]
+    fn test_process_directory() {
+        let dir = temp_dir("batch");
+        let input = dir.join("in");
+        let output = dir.join("out");
+        fs::create_dir_all(&input).unwrap();
+        for i in 0..8 {
+            write_scanner(
+                &input.join(format!("{i}.txt")),
+                &["457508000", "664371495", "888888888"],
+            );
+        }
+        // 2件目のエントリの行の長さが足りない
+        let mut broken: Vec<String> = fs::read_to_string(input.join("0.txt"))
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        broken[4].truncate(20);
+        fs::write(input.join("broken.txt"), broken.join("\n")).unwrap();
+
+        let options = BatchOptions {
+            workers: 3,
+            search: Some(SearchOptions::default()),
+            output: OutputFormat::Text,
+            normalize: false,
+            registry: None,
+        };
+        let results = process_directory(&input, &output, &AccountFormat::kata(), &options).unwrap();
+
+        assert_eq!(9, results.len());
+        assert_eq!(input.join("0.txt"), results[0].input);
+        assert_eq!(input.join("broken.txt"), results[8].input);
+        for result in &results[..8] {
+            assert!(result.is_ok(), "{:?}", result.error);
+            assert_eq!(
+                Summary {
+                    ok: 1,
+                    corrected: 1,
+                    amb: 1,
+                    ..Summary::default()
+                },
+                result.summary
+            );
+        }
+        assert_eq!(
+            "457508000\n664371485\n888888888 AMB ['888886888', '888888880', '888888988']\n",
+            fs::read_to_string(output.join("3.txt.report")).unwrap()
+        );
+        // 壊れたエントリを飛ばして、後のエントリも書き出す
+        assert!(results[8].is_ok(), "{:?}", results[8].error);
+        assert_eq!(
+            Summary {
+                ok: 1,
+                amb: 1,
+                broken: 1,
+                ..Summary::default()
+            },
+            results[8].summary
+        );
+        assert_eq!(
+            "457508000\n888888888 AMB ['888886888', '888888880', '888888988']\n",
+            fs::read_to_string(output.join("broken.txt.report")).unwrap()
+        );
+
+        let mut csv = Vec::new();
+        write_summary_csv(&mut csv, &results).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(10, csv.lines().count());
+        assert!(csv.lines().last().unwrap().ends_with(",1,0,0,0,1,0,1,"));
+
+        let mut json = Vec::new();
+        write_summary_json(&mut json, &results).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.ends_with(
+            r#""total":{"files":9,"failed":0,"ok":9,"corrected":8,"err":0,"ill":0,"amb":9,"reviewed":0,"broken":1}}
+"#
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_inside_input_dir() {
+        let dir = temp_dir("batch_nested");
+        let output = dir.join("out");
+        write_scanner(&dir.join("a.txt"), &["457508000"]);
+        let options = BatchOptions {
+            workers: 1,
+            ..BatchOptions::default()
+        };
+        let format = AccountFormat::kata();
+        for _ in 0..2 {
+            let results = process_directory(&dir, &output, &format, &options).unwrap();
+            assert_eq!(1, results.len());
+            fs::write(output.join(SUMMARY_CSV), "").unwrap();
+        }
+
+        // 入力先に書いても、前回のレポートと集計は読まない
+        for _ in 0..2 {
+            let results = process_directory(&dir, &dir, &format, &options).unwrap();
+            fs::write(dir.join(SUMMARY_CSV), "").unwrap();
+            fs::write(dir.join(SUMMARY_JSON), "").unwrap();
+            assert_eq!(
+                vec![dir.join("a.txt")],
+                results.iter().map(|r| r.input.clone()).collect::<Vec<_>>()
+            );
+        }
+        assert!(dir.join("a.txt.report").is_file());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    struct Panicking;
+
+    impl crate::checksum::Checksum for Panicking {
+        fn name(&self) -> &'static str {
+            "panicking"
+        }
+
+        fn is_valid(&self, _digits: &[u8]) -> bool {
+            panic!("checksum failed")
+        }
+    }
+
+    #[test]
+    fn test_panic_is_file_error() {
+        let dir = temp_dir("batch_panic");
+        let input = dir.join("in");
+        fs::create_dir_all(&input).unwrap();
+        write_scanner(&input.join("a.txt"), &["457508000"]);
+        let format = AccountFormat::new(9, Box::new(Panicking));
+        let options = BatchOptions {
+            workers: 2,
+            ..BatchOptions::default()
+        };
+        let results = process_directory(&input, &dir.join("out"), &format, &options).unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(
+            Some("panicked: checksum failed"),
+            results[0].error.as_deref()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
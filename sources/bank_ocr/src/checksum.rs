@@ -0,0 +1,260 @@
+use std::fmt;
+
+use crate::glyph_table::GlyphTable;
+use crate::layout::Layout;
+use crate::validator::{self, MissingDigit};
+
+/// A check-digit scheme for account numbers. `digits` are the values `0..=9`,
+/// most significant digit first.
+pub trait Checksum: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn is_valid(&self, digits: &[u8]) -> bool;
+
+    /// Solves for the single `?` of `account_number`, for schemes where that
+    /// is possible. Returns `None` by default.
+    fn solve_missing_digit(&self, _account_number: &str) -> Option<MissingDigit> {
+        None
+    }
+}
+
+/// The kata's scheme: `(d1 + 2*d2 + 3*d3 + ...) mod 11 = 0`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mod11;
+
+impl Checksum for Mod11 {
+    fn name(&self) -> &'static str {
+        "mod11"
+    }
+
+    fn is_valid(&self, digits: &[u8]) -> bool {
+        let check_sum: usize = digits
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, d)| *d as usize * (i + 1))
+            .sum();
+        check_sum.is_multiple_of(11)
+    }
+
+    fn solve_missing_digit(&self, account_number: &str) -> Option<MissingDigit> {
+        validator::solve_missing_digit(account_number)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Luhn;
+
+impl Checksum for Luhn {
+    fn name(&self) -> &'static str {
+        "luhn"
+    }
+
+    fn is_valid(&self, digits: &[u8]) -> bool {
+        let check_sum: u32 = digits
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, d)| {
+                let d = *d as u32;
+                match (i % 2, d * 2) {
+                    (0, _) => d,
+                    (_, doubled) if doubled > 9 => doubled - 9,
+                    (_, doubled) => doubled,
+                }
+            })
+            .sum();
+        check_sum.is_multiple_of(10)
+    }
+}
+
+/// ISO 7064 MOD 97-10, as used by IBAN: the number mod 97 must be 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mod97_10;
+
+impl Checksum for Mod97_10 {
+    fn name(&self) -> &'static str {
+        "mod97-10"
+    }
+
+    fn is_valid(&self, digits: &[u8]) -> bool {
+        let remainder = digits.iter().fold(0u32, |r, d| (r * 10 + *d as u32) % 97);
+        remainder == 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Verhoeff;
+
+const VERHOEFF_D: [[u8; 10]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [1, 2, 3, 4, 0, 6, 7, 8, 9, 5],
+    [2, 3, 4, 0, 1, 7, 8, 9, 5, 6],
+    [3, 4, 0, 1, 2, 8, 9, 5, 6, 7],
+    [4, 0, 1, 2, 3, 9, 5, 6, 7, 8],
+    [5, 9, 8, 7, 6, 0, 4, 3, 2, 1],
+    [6, 5, 9, 8, 7, 1, 0, 4, 3, 2],
+    [7, 6, 5, 9, 8, 2, 1, 0, 4, 3],
+    [8, 7, 6, 5, 9, 3, 2, 1, 0, 4],
+    [9, 8, 7, 6, 5, 4, 3, 2, 1, 0],
+];
+
+const VERHOEFF_P: [[u8; 10]; 8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [1, 5, 7, 6, 2, 8, 3, 0, 9, 4],
+    [5, 8, 0, 3, 7, 9, 6, 1, 4, 2],
+    [8, 9, 1, 6, 0, 4, 3, 5, 2, 7],
+    [9, 4, 5, 3, 1, 2, 6, 8, 7, 0],
+    [4, 2, 8, 6, 5, 7, 3, 9, 0, 1],
+    [2, 7, 9, 3, 8, 0, 6, 4, 1, 5],
+    [7, 0, 4, 6, 9, 1, 3, 2, 5, 8],
+];
+
+impl Checksum for Verhoeff {
+    fn name(&self) -> &'static str {
+        "verhoeff"
+    }
+
+    fn is_valid(&self, digits: &[u8]) -> bool {
+        let check = digits.iter().rev().enumerate().fold(0u8, |c, (i, d)| {
+            VERHOEFF_D[c as usize][VERHOEFF_P[i % 8][*d as usize] as usize]
+        });
+        check == 0
+    }
+}
+
+/// Returns the scheme called `name`, e.g. `luhn`.
+pub fn checksum_by_name(name: &str) -> Option<Box<dyn Checksum>> {
+    match name {
+        "mod11" => Some(Box::new(Mod11)),
+        "luhn" => Some(Box::new(Luhn)),
+        "mod97-10" => Some(Box::new(Mod97_10)),
+        "verhoeff" => Some(Box::new(Verhoeff)),
+        _ => None,
+    }
+}
+
+/// The shape of the account numbers of one bank: how many digits, which checksum
+/// and how its scanner draws them.
+pub struct AccountFormat {
+    pub length: usize,
+    pub checksum: Box<dyn Checksum>,
+    pub layout: Layout,
+    /// The glyphs the scanner prints, `GlyphTable::digits()` unless set.
+    pub table: GlyphTable,
+}
+
+impl AccountFormat {
+    pub fn new(length: usize, checksum: Box<dyn Checksum>) -> Self {
+        Self {
+            length,
+            checksum,
+            layout: Layout::kata(),
+            table: GlyphTable::digits(),
+        }
+    }
+
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_table(mut self, table: GlyphTable) -> Self {
+        self.table = table;
+        self
+    }
+
+    /// Nine digits with the mod 11 checksum of the kata.
+    pub fn kata() -> Self {
+        Self::new(9, Box::new(Mod11))
+    }
+
+    /// Returns `true` if `account_number` has `length` digits and a valid checksum.
+    pub fn is_valid(&self, account_number: &str) -> bool {
+        let digits: Option<Vec<u8>> = account_number
+            .chars()
+            .map(|c| c.to_digit(10).map(|d| d as u8))
+            .collect();
+        match digits {
+            Some(digits) => digits.len() == self.length && self.checksum.is_valid(&digits),
+            None => false,
+        }
+    }
+
+    /// The width of an entry line, `layout.glyph_width` columns per digit.
+    pub fn width(&self) -> usize {
+        self.layout.width(self.length)
+    }
+}
+
+impl Default for AccountFormat {
+    fn default() -> Self {
+        Self::kata()
+    }
+}
+
+impl fmt::Debug for AccountFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccountFormat")
+            .field("length", &self.length)
+            .field("checksum", &self.checksum.name())
+            .field("layout", &self.layout)
+            .field("table", &self.table)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests_checksum {
+    use super::*;
+
+    fn digits(s: &str) -> Vec<u8> {
+        s.bytes().map(|b| b - b'0').collect()
+    }
+
+    #[test]
+    fn test_mod11() {
+        assert!(Mod11.is_valid(&digits("345882865")));
+        assert!(!Mod11.is_valid(&digits("664371495")));
+    }
+
+    #[test]
+    fn test_luhn() {
+        assert!(Luhn.is_valid(&digits("79927398713")));
+        assert!(!Luhn.is_valid(&digits("79927398710")));
+    }
+
+    #[test]
+    fn test_mod97_10() {
+        // GB82 WEST 1234 5698 7654 32 を数字に変換したもの
+        assert!(Mod97_10.is_valid(&digits("3214282912345698765432161182")));
+        assert!(!Mod97_10.is_valid(&digits("3214282912345698765432161183")));
+    }
+
+    #[test]
+    fn test_verhoeff() {
+        assert!(Verhoeff.is_valid(&digits("2363")));
+        assert!(!Verhoeff.is_valid(&digits("2364")));
+        // 隣り合う桁の入れ替えも検出できる
+        assert!(!Verhoeff.is_valid(&digits("3263")));
+    }
+
+    #[test]
+    fn test_checksum_by_name() {
+        for name in ["mod11", "luhn", "mod97-10", "verhoeff"] {
+            assert_eq!(name, checksum_by_name(name).unwrap().name());
+        }
+        assert!(checksum_by_name("crc32").is_none());
+    }
+
+    #[test]
+    fn test_account_format() {
+        let format = AccountFormat::new(11, Box::new(Luhn));
+        assert_eq!(33, format.width());
+        let format = format.with_layout(Layout::new(4, 4).unwrap());
+        assert_eq!(44, format.width());
+        assert!(format.is_valid("79927398713"));
+        assert!(!format.is_valid("7992739871"));
+        assert!(!format.is_valid("7992739871?"));
+        assert!(AccountFormat::kata().is_valid("457508000"));
+    }
+}
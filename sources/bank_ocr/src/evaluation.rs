@@ -0,0 +1,358 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::report::{EntryReport, ReportStatus};
+
+/// The columns of a confusion matrix: the digits, then `?`.
+pub const RECOGNIZED: [char; 11] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '?'];
+
+/// The statuses in the order they are reported.
+pub const STATUSES: [ReportStatus; 6] = [
+    ReportStatus::Ok,
+    ReportStatus::Corrected,
+    ReportStatus::Err,
+    ReportStatus::Ill,
+    ReportStatus::Amb,
+    ReportStatus::Reviewed,
+];
+
+/// How often each expected digit was recognized as each digit or `?`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfusionMatrix {
+    counts: [[usize; 11]; 10],
+}
+
+impl ConfusionMatrix {
+    /// Counts one digit; other expected characters are ignored, and recognized
+    /// characters other than digits count as `?`.
+    pub fn record(&mut self, expected: char, recognized: char) {
+        let Some(row) = expected.to_digit(10) else {
+            return;
+        };
+        let column = recognized.to_digit(10).unwrap_or(10);
+        self.counts[row as usize][column as usize] += 1;
+    }
+
+    pub fn get(&self, expected: char, recognized: char) -> usize {
+        match (
+            expected.to_digit(10),
+            RECOGNIZED.iter().position(|&c| c == recognized),
+        ) {
+            (Some(row), Some(column)) => self.counts[row as usize][column],
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for ConfusionMatrix {
+    /// Rows are the expected digits and columns what was recognized.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "   ")?;
+        for c in RECOGNIZED {
+            write!(f, "{c:>6}")?;
+        }
+        for (digit, row) in self.counts.iter().enumerate() {
+            write!(f, "\n{digit:>3}")?;
+            for count in row {
+                write!(f, "{count:>6}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entries of one status and how many of them ended with the expected number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub total: usize,
+    pub correct: usize,
+}
+
+impl Tally {
+    fn add(&mut self, correct: bool) {
+        self.total += 1;
+        self.correct += usize::from(correct);
+    }
+
+    /// The share of correct items, or `None` when there are none.
+    pub fn accuracy(&self) -> Option<f64> {
+        (self.total > 0).then(|| self.correct as f64 / self.total as f64)
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.correct, self.total)?;
+        if let Some(accuracy) = self.accuracy() {
+            write!(f, " ({:.2}%)", accuracy * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum EvaluationError {
+    Io(io::Error),
+    /// A line of the expected numbers is not an account number of the right length.
+    InvalidNumber {
+        line_number: usize,
+        number: String,
+    },
+    /// The expected numbers and the scanned entries differ in number.
+    CountMismatch {
+        entries: usize,
+        expected: usize,
+    },
+}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvaluationError::Io(e) => write!(f, "could not read expected numbers: {e}"),
+            EvaluationError::InvalidNumber {
+                line_number,
+                number,
+            } => write!(f, "line {line_number}: invalid account number {number:?}"),
+            EvaluationError::CountMismatch { entries, expected } => write!(
+                f,
+                "{entries} entries were scanned but {expected} numbers are expected"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EvaluationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EvaluationError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EvaluationError {
+    fn from(e: io::Error) -> Self {
+        EvaluationError::Io(e)
+    }
+}
+
+/// Reads the expected account numbers of `length` digits, one per line; blank
+/// lines are skipped.
+pub fn read_expected<R: BufRead>(reader: R, length: usize) -> Result<Vec<String>, EvaluationError> {
+    let mut expected = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let number = line.trim();
+        if number.is_empty() {
+            continue;
+        }
+        if number.len() != length || !number.chars().all(|c| c.is_ascii_digit()) {
+            return Err(EvaluationError::InvalidNumber {
+                line_number: i + 1,
+                number: number.to_string(),
+            });
+        }
+        expected.push(number.to_string());
+    }
+    Ok(expected)
+}
+
+/// Recognition accuracy against known account numbers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Evaluation {
+    /// Recognized digits, before correction, compared position by position.
+    pub digits: Tally,
+    pub confusion: ConfusionMatrix,
+    /// Entries whose final number is the expected one.
+    pub entries: Tally,
+    /// `entries` per status, in the order of `STATUSES`.
+    pub statuses: [Tally; 6],
+    /// Entries the correction changed, and how often it chose the expected number.
+    pub corrections: Tally,
+}
+
+impl Evaluation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compares one processed entry with the number it should have been, as
+    /// read by `read_expected`.
+    pub fn record(&mut self, report: &EntryReport, expected: &str) {
+        for (e, r) in expected
+            .chars()
+            .zip(report.raw.digits().iter().map(|d| d.to_char()))
+        {
+            self.confusion.record(e, r);
+            self.digits.add(e == r);
+        }
+        let correct = report.account_number == expected;
+        self.entries.add(correct);
+        let status = STATUSES.iter().position(|&s| s == report.status);
+        if let Some(i) = status {
+            self.statuses[i].add(correct);
+        }
+        if report.status == ReportStatus::Corrected {
+            self.corrections.add(correct);
+        }
+    }
+
+    /// Evaluates `reports` against `expected`, which must be as many.
+    pub fn evaluate<I>(reports: I, expected: &[String]) -> Result<Self, EvaluationError>
+    where
+        I: IntoIterator<Item = EntryReport>,
+    {
+        let mut evaluation = Self::new();
+        let mut count = 0;
+        for report in reports {
+            if let Some(expected) = expected.get(count) {
+                evaluation.record(&report, expected);
+            }
+            count += 1;
+        }
+        if count != expected.len() {
+            return Err(EvaluationError::CountMismatch {
+                entries: count,
+                expected: expected.len(),
+            });
+        }
+        Ok(evaluation)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "digits: {}", self.digits)?;
+        writeln!(writer, "entries: {}", self.entries)?;
+        for (status, tally) in STATUSES.iter().zip(&self.statuses) {
+            if tally.total > 0 {
+                writeln!(writer, "  {}: {tally}", status.label())?;
+            }
+        }
+        writeln!(writer, "corrections: {}", self.corrections)?;
+        writeln!(writer)?;
+        writeln!(writer, "confusion (rows: expected, columns: recognized)")?;
+        writeln!(writer, "{}", self.confusion)
+    }
+}
+
+#[cfg(test)]
+mod tests_evaluation {
+    use super::*;
+    use crate::checksum::AccountFormat;
+    use crate::parser::parse_entries;
+    use crate::renderer::render_account_number;
+    use crate::report::report_entry;
+    use crate::search::SearchOptions;
+
+    // 490067715 (AMB), ?23456789 (123456789 に訂正), 000000051 (OK)
+    const SCAN: &str = "    _  _  _  _  _  _     _ \n|_||_|| || ||_   |  |  ||_ \n  | _||_||_||_|  |  |  | _|\n\n    _  _     _  _  _  _  _ \n _| _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n\n";
+
+    fn reports() -> Vec<EntryReport> {
+        let format = AccountFormat::kata();
+        let search = SearchOptions::default();
+        let scan = SCAN.to_string() + &render_account_number("000000051").unwrap();
+        parse_entries(scan.as_bytes())
+            .map(|entry| report_entry(&entry.unwrap(), None, &format, Some(&search)))
+            .collect()
+    }
+
+    #[test]
+    fn test_confusion_matrix() {
+        let mut matrix = ConfusionMatrix::default();
+        matrix.record('8', '8');
+        matrix.record('8', '0');
+        matrix.record('1', '?');
+        matrix.record('1', 'x');
+        matrix.record('-', '1');
+        assert_eq!(1, matrix.get('8', '0'));
+        assert_eq!(2, matrix.get('1', '?'));
+        assert_eq!(0, matrix.get('-', '1'));
+
+        let text = matrix.to_string();
+        assert_eq!(11, text.lines().count());
+        assert!(text.lines().nth(9).unwrap().starts_with("  8     1"));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let expected: Vec<String> =
+            read_expected("490867715\n\n123456789\n000000051\n".as_bytes(), 9).unwrap();
+        let evaluation = Evaluation::evaluate(reports(), &expected).unwrap();
+
+        // 490067715 の 0 と ? の2桁が違う
+        assert_eq!(
+            Tally {
+                total: 27,
+                correct: 25
+            },
+            evaluation.digits
+        );
+        assert_eq!(1, evaluation.confusion.get('8', '0'));
+        assert_eq!(1, evaluation.confusion.get('1', '?'));
+        assert_eq!(
+            Tally {
+                total: 3,
+                correct: 2
+            },
+            evaluation.entries
+        );
+        assert_eq!(
+            Tally {
+                total: 1,
+                correct: 1
+            },
+            evaluation.statuses[0]
+        );
+        assert_eq!(
+            Tally {
+                total: 1,
+                correct: 0
+            },
+            evaluation.statuses[4]
+        );
+        assert_eq!(
+            Tally {
+                total: 1,
+                correct: 1
+            },
+            evaluation.corrections
+        );
+
+        let mut text = Vec::new();
+        evaluation.write_to(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with(
+            "digits: 25/27 (92.59%)\nentries: 2/3 (66.67%)\n  OK: 1/1 (100.00%)\n  CORRECTED: 1/1 (100.00%)\n  AMB: 0/1 (0.00%)\ncorrections: 1/1 (100.00%)\n"
+        ));
+    }
+
+    #[test]
+    fn test_count_mismatch() {
+        let expected = vec!["490867715".to_string()];
+        assert!(matches!(
+            Evaluation::evaluate(reports(), &expected),
+            Err(EvaluationError::CountMismatch {
+                entries: 3,
+                expected: 1
+            })
+        ));
+        assert_eq!(None, Tally::default().accuracy());
+    }
+
+    #[test]
+    fn test_invalid_expected() {
+        for (file, line) in [
+            ("490867715\n\n12345678\n", 3),
+            ("490867715\n49086771?\n", 2),
+            ("4908677150\n", 1),
+        ] {
+            assert!(matches!(
+                read_expected(file.as_bytes(), 9),
+                Err(EvaluationError::InvalidNumber { line_number, .. }) if line_number == line
+            ));
+        }
+        assert_eq!(
+            vec!["2363"],
+            read_expected(" 2363 \n".as_bytes(), 4).unwrap()
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::account_number::Glyph;
+use crate::segments::{DIGIT_SEGMENTS, G, SegmentError, Segments};
+
+/// The hexadecimal letters as printed by seven-segment displays, bits `gfedcba`.
+pub const HEX_SEGMENTS: [(char, Segments); 6] = [
+    ('A', Segments::from_bits(0b111_0111)),
+    ('b', Segments::from_bits(0b111_1100)),
+    ('C', Segments::from_bits(0b011_1001)),
+    ('d', Segments::from_bits(0b101_1110)),
+    ('E', Segments::from_bits(0b111_1001)),
+    ('F', Segments::from_bits(0b111_0001)),
+];
+
+pub const DASH_SEGMENTS: Segments = G;
+
+#[derive(Debug)]
+pub enum GlyphTableError {
+    Io(io::Error),
+    /// The header line of a block must hold one character every three columns.
+    InvalidHeader {
+        line_number: usize,
+    },
+    /// A block ended before its three lines of glyphs.
+    TruncatedBlock {
+        line_number: usize,
+    },
+    InvalidGlyph {
+        line_number: usize,
+        character: char,
+        error: SegmentError,
+    },
+}
+
+impl fmt::Display for GlyphTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlyphTableError::Io(e) => write!(f, "could not read glyph table: {e}"),
+            GlyphTableError::InvalidHeader { line_number } => {
+                write!(
+                    f,
+                    "line {line_number}: expected one character every 3 columns"
+                )
+            }
+            GlyphTableError::TruncatedBlock { line_number } => {
+                write!(f, "line {line_number}: expected 3 lines of glyphs")
+            }
+            GlyphTableError::InvalidGlyph {
+                line_number,
+                character,
+                error,
+            } => write!(f, "line {line_number}: glyph of {character:?}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for GlyphTableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GlyphTableError::Io(e) => Some(e),
+            GlyphTableError::InvalidGlyph { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GlyphTableError {
+    fn from(e: io::Error) -> Self {
+        GlyphTableError::Io(e)
+    }
+}
+
+/// Maps characters to the segments used to draw them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlyphTable {
+    glyphs: Vec<(char, Segments)>,
+}
+
+impl GlyphTable {
+    pub fn new() -> Self {
+        Self { glyphs: Vec::new() }
+    }
+
+    /// The glyphs for `0` to `9` used by the bank's scanner.
+    pub fn digits() -> Self {
+        let mut table = Self::new();
+        for (c, segments) in ('0'..='9').zip(DIGIT_SEGMENTS) {
+            table.insert(c, segments);
+        }
+        table
+    }
+
+    /// `0` to `9` and `A b C d E F`.
+    pub fn hex() -> Self {
+        let mut table = Self::digits();
+        for (c, segments) in HEX_SEGMENTS {
+            table.insert(c, segments);
+        }
+        table
+    }
+
+    /// `0` to `9` and `-`.
+    pub fn dash() -> Self {
+        let mut table = Self::digits();
+        table.insert('-', DASH_SEGMENTS);
+        table
+    }
+
+    /// Returns the preset called `name`: `digits`, `hex` or `dash`.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "digits" => Some(Self::digits()),
+            "hex" => Some(Self::hex()),
+            "dash" => Some(Self::dash()),
+            _ => None,
+        }
+    }
+
+    /// Reads a table drawn in ASCII art. Each block is a header line naming one
+    /// character every three columns, followed by three lines drawing them, in
+    /// the same layout as a scanner entry. Blocks are separated by blank lines.
+    ///
+    /// ```text
+    ///  A  b  -
+    ///  _
+    /// |_||_  _
+    /// | ||_|
+    /// ```
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, GlyphTableError> {
+        let mut table = Self::new();
+        let mut lines = reader.lines().enumerate();
+        while let Some((i, header)) = lines.next() {
+            let header = header?;
+            if header.trim().is_empty() {
+                continue;
+            }
+            let line_number = i + 1;
+            let characters =
+                header_characters(&header).ok_or(GlyphTableError::InvalidHeader { line_number })?;
+
+            let mut rows = Vec::new();
+            for _ in 0..3 {
+                match lines.next() {
+                    Some((_, line)) => rows.push(line?.chars().collect::<Vec<char>>()),
+                    None => {
+                        return Err(GlyphTableError::TruncatedBlock {
+                            line_number: line_number + rows.len() + 1,
+                        });
+                    }
+                }
+            }
+
+            for (k, &character) in characters.iter().enumerate() {
+                let mut glyph: Glyph = [[' '; 3]; 3];
+                for (glyph_row, row) in glyph.iter_mut().zip(&rows) {
+                    for (j, cell) in glyph_row.iter_mut().enumerate() {
+                        // 行末の空白は省略されていてもよい
+                        *cell = row.get(k * 3 + j).copied().unwrap_or(' ');
+                    }
+                }
+                let segments = Segments::from_glyph(&glyph).map_err(|error| {
+                    GlyphTableError::InvalidGlyph {
+                        line_number,
+                        character,
+                        error,
+                    }
+                })?;
+                table.insert(character, segments);
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GlyphTableError> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Adds a glyph, replacing the previous glyph of `c`.
+    pub fn insert(&mut self, c: char, segments: Segments) {
+        match self.glyphs.iter_mut().find(|(k, _)| *k == c) {
+            Some(entry) => entry.1 = segments,
+            None => self.glyphs.push((c, segments)),
+        }
+    }
+
+    pub fn segments(&self, c: char) -> Option<Segments> {
+        self.glyphs.iter().find(|(k, _)| *k == c).map(|(_, s)| *s)
+    }
+
+    pub fn glyph(&self, c: char) -> Option<Glyph> {
+        self.segments(c).map(Segments::to_glyph)
+    }
+
+    /// Returns the character drawn by `glyph`, or `?` if the table has none.
+    pub fn recognize(&self, glyph: &Glyph) -> char {
+        Segments::from_glyph(glyph)
+            .ok()
+            .and_then(|segments| self.glyphs.iter().find(|(_, s)| *s == segments))
+            .map_or('?', |(c, _)| *c)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (char, Segments)> + '_ {
+        self.glyphs.iter().copied()
+    }
+}
+
+/// The characters at columns 1, 4, 7, ... of a header line; all other columns must be blank.
+fn header_characters(header: &str) -> Option<Vec<char>> {
+    let header: Vec<char> = header.trim_end().chars().collect();
+    let mut characters = Vec::new();
+    for (column, &c) in header.iter().enumerate() {
+        match (column % 3 == 1, c == ' ') {
+            (true, false) => characters.push(c),
+            (false, true) => {}
+            _ => return None,
+        }
+    }
+    Some(characters)
+}
+
+impl Default for GlyphTable {
+    fn default() -> Self {
+        Self::digits()
+    }
+}
+
+#[cfg(test)]
+mod tests_glyph_table {
+    use super::*;
+    use crate::recognizer::{EIGHT_PATTERN, ONE_PATTERN};
+
+    #[test]
+    fn test_digits() {
+        let table = GlyphTable::digits();
+        assert_eq!(Some(ONE_PATTERN), table.glyph('1'));
+        assert_eq!(Some(DIGIT_SEGMENTS[1]), table.segments('1'));
+        assert_eq!(None, table.glyph('A'));
+        assert_eq!(10, table.iter().count());
+    }
+
+    #[test]
+    fn test_insert_replaces_glyph() {
+        let mut table = GlyphTable::digits();
+        table.insert('1', DIGIT_SEGMENTS[7]);
+        assert_eq!(Some(DIGIT_SEGMENTS[7]), table.segments('1'));
+        assert_eq!(10, table.iter().count());
+    }
+
+    #[test]
+    fn test_hex_segments() {
+        use crate::segments::{A, B, C, D, E, F};
+
+        let drawn = |c| HEX_SEGMENTS.iter().find(|(k, _)| *k == c).unwrap().1;
+        assert_eq!(A.union(B).union(C).union(E).union(F).union(G), drawn('A'));
+        assert_eq!(C.union(D).union(E).union(F).union(G), drawn('b'));
+        assert_eq!(A.union(D).union(E).union(F), drawn('C'));
+        assert_eq!(B.union(C).union(D).union(E).union(G), drawn('d'));
+        assert_eq!(A.union(D).union(E).union(F).union(G), drawn('E'));
+        assert_eq!(A.union(E).union(F).union(G), drawn('F'));
+    }
+
+    #[test]
+    fn test_presets() {
+        let hex = GlyphTable::preset("hex").unwrap();
+        assert_eq!(16, hex.iter().count());
+        assert_eq!('8', hex.recognize(&EIGHT_PATTERN));
+        let b = [[' ', ' ', ' '], ['|', '_', ' '], ['|', '_', '|']];
+        assert_eq!('b', hex.recognize(&b));
+        assert_eq!('?', GlyphTable::digits().recognize(&b));
+
+        let dash = GlyphTable::preset("dash").unwrap();
+        assert_eq!('-', dash.recognize(&[[' '; 3], [' ', '_', ' '], [' '; 3]]));
+        assert!(GlyphTable::preset("octal").is_none());
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let text = "\n A  b  -\n _\n|_||_  _\n| ||_|\n\n 1\n\n  |\n  |\n";
+        let table = GlyphTable::from_reader(text.as_bytes()).unwrap();
+        assert_eq!(
+            vec![
+                ('A', HEX_SEGMENTS[0].1),
+                ('b', HEX_SEGMENTS[1].1),
+                ('-', DASH_SEGMENTS),
+                ('1', DIGIT_SEGMENTS[1]),
+            ],
+            table.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_from_reader_errors() {
+        assert!(matches!(
+            GlyphTable::from_reader("AB\n".as_bytes()),
+            Err(GlyphTableError::InvalidHeader { line_number: 1 })
+        ));
+        assert!(matches!(
+            GlyphTable::from_reader(" A\n _\n".as_bytes()),
+            Err(GlyphTableError::TruncatedBlock { line_number: 3 })
+        ));
+        assert!(matches!(
+            GlyphTable::from_reader(" A\n |\n   \n   \n".as_bytes()),
+            Err(GlyphTableError::InvalidGlyph {
+                line_number: 1,
+                character: 'A',
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,652 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use crate::parser::{DIGIT_LINES, Entry};
+use crate::segments::{SEGMENT_CELLS, Segments};
+
+/// An 8-bit grayscale image, row by row; 0 is black and 255 is white.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Which pixels of an image are ink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    ink: Vec<bool>,
+}
+
+/// The size in pixels of one character cell of the scanner's 3x3 grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// The file does not start with the magic number of a supported format.
+    UnsupportedFormat,
+    InvalidHeader,
+    /// The file ends before all pixels are read.
+    TruncatedData,
+    #[cfg(feature = "png")]
+    Png(png::DecodingError),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "could not read image: {e}"),
+            ImageError::UnsupportedFormat => write!(f, "not a PBM, PGM or PNG image"),
+            ImageError::InvalidHeader => write!(f, "invalid image header"),
+            ImageError::TruncatedData => write!(f, "image data is truncated"),
+            #[cfg(feature = "png")]
+            ImageError::Png(e) => write!(f, "could not decode PNG: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(e) => Some(e),
+            #[cfg(feature = "png")]
+            ImageError::Png(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(e: io::Error) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+impl GrayImage {
+    /// A white image.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![255; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        self.pixels[y * self.width + x] = value;
+    }
+
+    /// The gray level that best separates ink from paper (Otsu's method).
+    pub fn otsu_threshold(&self) -> u8 {
+        let mut histogram = [0usize; 256];
+        for &p in &self.pixels {
+            histogram[p as usize] += 1;
+        }
+        let total = self.pixels.len() as f64;
+        let sum: f64 = histogram
+            .iter()
+            .enumerate()
+            .map(|(level, &n)| level as f64 * n as f64)
+            .sum();
+
+        let mut best = (0.0, 0u8);
+        let (mut dark, mut dark_sum) = (0.0, 0.0);
+        for (level, &n) in histogram.iter().enumerate() {
+            dark += n as f64;
+            dark_sum += level as f64 * n as f64;
+            let light = total - dark;
+            if dark == 0.0 || light == 0.0 {
+                continue;
+            }
+            let between = dark * light * (dark_sum / dark - (sum - dark_sum) / light).powi(2);
+            if between > best.0 {
+                best = (between, level as u8);
+            }
+        }
+        best.1
+    }
+
+    /// Pixels at or below the Otsu threshold are ink.
+    pub fn binarize(&self) -> Bitmap {
+        let threshold = self.otsu_threshold();
+        Bitmap {
+            width: self.width,
+            height: self.height,
+            ink: self.pixels.iter().map(|&p| p <= threshold).collect(),
+        }
+    }
+}
+
+impl Bitmap {
+    pub fn is_ink(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.ink[y * self.width + x]
+    }
+
+    fn ink_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.ink
+            .iter()
+            .enumerate()
+            .filter(|(_, ink)| **ink)
+            .map(|(i, _)| (i % self.width, i / self.width))
+    }
+}
+
+/// Reads a PBM (`P1`, `P4`) or PGM (`P2`, `P5`) image.
+pub fn read_pnm<R: Read>(mut reader: R) -> Result<GrayImage, ImageError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut header = PnmHeader { data: &data, at: 0 };
+
+    let magic = header.token().ok_or(ImageError::UnsupportedFormat)?;
+    let (binary, bitmap) = match magic {
+        b"P1" => (false, true),
+        b"P2" => (false, false),
+        b"P4" => (true, true),
+        b"P5" => (true, false),
+        _ => return Err(ImageError::UnsupportedFormat),
+    };
+    let width = header.number()?;
+    let height = header.number()?;
+    let max = if bitmap { 1 } else { header.number()? };
+    if max == 0 || max > 255 {
+        return Err(ImageError::InvalidHeader);
+    }
+
+    // 画素は少なくとも1バイト（P4 は1ビット）なので、足りなければ確保する前に断る
+    let needed = if binary && bitmap {
+        width.div_ceil(8).checked_mul(height)
+    } else {
+        width.checked_mul(height)
+    };
+    if needed.is_none_or(|n| n > data.len() - header.at) {
+        return Err(ImageError::TruncatedData);
+    }
+
+    let mut image = GrayImage::new(width, height);
+    // PBM は 1 が黒、PGM は 0 が黒
+    let level = |value: usize| -> u8 {
+        if bitmap {
+            if value == 0 { 255 } else { 0 }
+        } else {
+            (value * 255 / max) as u8
+        }
+    };
+
+    if binary {
+        // ヘッダーの後の空白1文字の次から画素が始まる
+        let pixels = data.get(header.at + 1..).unwrap_or(&[]);
+        if bitmap {
+            let row_bytes = width.div_ceil(8);
+            if pixels.len() < row_bytes * height {
+                return Err(ImageError::TruncatedData);
+            }
+            for y in 0..height {
+                for x in 0..width {
+                    let byte = pixels[y * row_bytes + x / 8];
+                    image.set(x, y, level(((byte >> (7 - x % 8)) & 1) as usize));
+                }
+            }
+        } else {
+            if pixels.len() < width * height {
+                return Err(ImageError::TruncatedData);
+            }
+            for (i, &p) in pixels[..width * height].iter().enumerate() {
+                image.pixels[i] = level(p as usize);
+            }
+        }
+    } else {
+        for i in 0..width * height {
+            let value = if bitmap {
+                header.bit()?
+            } else {
+                header.number().map_err(|_| ImageError::TruncatedData)?
+            };
+            image.pixels[i] = level(value);
+        }
+    }
+    Ok(image)
+}
+
+struct PnmHeader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> PnmHeader<'a> {
+    /// Skips whitespace and `#` comments.
+    fn skip_blank(&mut self) {
+        while let Some(&c) = self.data.get(self.at) {
+            if c == b'#' {
+                while self.data.get(self.at).is_some_and(|&c| c != b'\n') {
+                    self.at += 1;
+                }
+            } else if c.is_ascii_whitespace() {
+                self.at += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> Option<&'a [u8]> {
+        self.skip_blank();
+        let start = self.at;
+        while self
+            .data
+            .get(self.at)
+            .is_some_and(|c| !c.is_ascii_whitespace() && *c != b'#')
+        {
+            self.at += 1;
+        }
+        (self.at > start).then(|| &self.data[start..self.at])
+    }
+
+    fn number(&mut self) -> Result<usize, ImageError> {
+        self.token()
+            .and_then(|t| std::str::from_utf8(t).ok())
+            .and_then(|t| t.parse().ok())
+            .ok_or(ImageError::InvalidHeader)
+    }
+
+    /// Plain PBM may write pixels without spaces between them.
+    fn bit(&mut self) -> Result<usize, ImageError> {
+        self.skip_blank();
+        let bit = match self.data.get(self.at) {
+            Some(b'0') => 0,
+            Some(b'1') => 1,
+            _ => return Err(ImageError::TruncatedData),
+        };
+        self.at += 1;
+        Ok(bit)
+    }
+}
+
+/// Writes a binary PGM (`P5`) image.
+pub fn write_pgm<W: Write>(writer: &mut W, image: &GrayImage) -> io::Result<()> {
+    write!(writer, "P5\n{} {}\n255\n", image.width, image.height)?;
+    writer.write_all(&image.pixels)
+}
+
+/// Reads a PNG image as grayscale.
+#[cfg(feature = "png")]
+pub fn read_png<R: Read>(reader: R) -> Result<GrayImage, ImageError> {
+    let mut decoder = png::Decoder::new(BufReader::new(reader));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(ImageError::Png)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(ImageError::Png)?;
+    let channels = info.color_type.samples();
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut image = GrayImage::new(width, height);
+    for (i, pixel) in buffer[..info.buffer_size()].chunks(channels).enumerate() {
+        image.pixels[i] = match pixel {
+            [gray] | [gray, _] => *gray,
+            [r, g, b, ..] => ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000) as u8,
+            [] => 255,
+        };
+    }
+    Ok(image)
+}
+
+/// Reads a PBM or PGM image, or a PNG image with the `png` feature, by its magic number.
+pub fn read_image(path: impl AsRef<Path>) -> Result<GrayImage, ImageError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 2];
+    reader.read_exact(&mut magic)?;
+    let reader = magic.chain(reader);
+    match &magic {
+        b"P1" | b"P2" | b"P4" | b"P5" => read_pnm(reader),
+        #[cfg(feature = "png")]
+        [0x89, b'P'] => read_png(reader),
+        _ => Err(ImageError::UnsupportedFormat),
+    }
+}
+
+/// The thickness of a pipe or underscore drawn in a cell.
+fn stroke(cell: CellSize) -> usize {
+    (cell.width.min(cell.height) / 4).max(1)
+}
+
+/// The rectangle `(x, y, width, height)`, relative to its cell, where a pipe or
+/// an underscore is drawn.
+fn stroke_rect(c: char, cell: CellSize) -> (usize, usize, usize, usize) {
+    let t = stroke(cell);
+    match c {
+        '_' => (0, cell.height - t, cell.width, t),
+        _ => ((cell.width - t) / 2, 0, t, cell.height),
+    }
+}
+
+/// Draws scanner entries as an image, each character filling a `cell`, with a
+/// white `margin` around. This is how fixtures for the image front-end are made.
+pub fn render_image(text: &str, cell: CellSize, margin: usize) -> GrayImage {
+    let lines: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+    let columns = lines.iter().map(Vec::len).max().unwrap_or(0);
+    let mut image = GrayImage::new(
+        columns * cell.width + 2 * margin,
+        lines.len() * cell.height + 2 * margin,
+    );
+    for (row, line) in lines.iter().enumerate() {
+        for (column, &c) in line.iter().enumerate() {
+            if c != '_' && c != '|' {
+                continue;
+            }
+            let (x, y, w, h) = stroke_rect(c, cell);
+            for dy in 0..h {
+                for dx in 0..w {
+                    image.set(
+                        margin + column * cell.width + x + dx,
+                        margin + row * cell.height + y + dy,
+                        0,
+                    );
+                }
+            }
+        }
+    }
+    image
+}
+
+/// The digits found in an image, one `Vec` per entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageScan {
+    /// The top left pixel of the first digit.
+    pub origin: (usize, usize),
+    pub entries: Vec<Vec<Segments>>,
+}
+
+impl ImageScan {
+    /// The entries as if read from a scanner file, for the rest of the pipeline.
+    /// `line_number` counts rows of cells.
+    pub fn to_entries(&self) -> Vec<Entry> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, digits)| {
+                let mut lines = vec![String::new(); DIGIT_LINES];
+                for segments in digits {
+                    for (line, row) in lines.iter_mut().zip(segments.to_glyph()) {
+                        line.extend(row);
+                    }
+                }
+                Entry {
+                    index,
+                    line_number: index * (DIGIT_LINES + 1) + 1,
+                    lines,
+                    normalizations: Vec::new(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether a pixel at `(u, v)` from the origin lies where a segment may be drawn.
+fn in_stroke(u: usize, v: usize, cell: CellSize) -> bool {
+    let entry_height = cell.height * (DIGIT_LINES + 1);
+    let row = (v % entry_height) / cell.height;
+    let column = (u / cell.width) % 3;
+    let (px, py) = (u % cell.width, v % cell.height);
+    SEGMENT_CELLS
+        .iter()
+        .filter(|(_, r, c, _)| *r == row && *c == column)
+        .any(|(_, _, _, c)| {
+            let (x, y, w, h) = stroke_rect(*c, cell);
+            (x..x + w).contains(&px) && (y..y + h).contains(&py)
+        })
+}
+
+/// Finds the origin of the digit grid: the alignment, within one digit and one
+/// entry, under which the most ink falls where segments are drawn.
+pub fn locate_grid(bitmap: &Bitmap, cell: CellSize) -> Option<(usize, usize)> {
+    let ink: Vec<(usize, usize)> = bitmap.ink_pixels().collect();
+    let x_min = ink.iter().map(|p| p.0).min()?;
+    let y_min = ink.iter().map(|p| p.1).min()?;
+    let (digit_width, entry_height) = (cell.width * 3, cell.height * (DIGIT_LINES + 1));
+
+    let mut best = (0, (0, 0));
+    for dy in 0..entry_height {
+        for dx in 0..digit_width {
+            // dx, dy だけずらした格子のうち、最初のインクを含む桁とエントリから始まるもの
+            let (Some(origin_x), Some(origin_y)) = (
+                x_min.checked_sub((x_min + digit_width - dx) % digit_width),
+                y_min.checked_sub((y_min + entry_height - dy) % entry_height),
+            ) else {
+                continue;
+            };
+            let inside = ink
+                .iter()
+                .filter(|(x, y)| in_stroke(x - origin_x, y - origin_y, cell))
+                .count();
+            if inside > best.0 {
+                best = (inside, (origin_x, origin_y));
+            }
+        }
+    }
+    Some(best.1)
+}
+
+/// Binarizes `image`, locates the grid and reads `digits` digits per entry.
+/// A segment is on when at least half of its stroke is ink.
+pub fn scan_image(image: &GrayImage, cell: CellSize, digits: usize) -> ImageScan {
+    let bitmap = image.binarize();
+    let Some(origin) = locate_grid(&bitmap, cell) else {
+        return ImageScan {
+            origin: (0, 0),
+            entries: Vec::new(),
+        };
+    };
+    let y_max = bitmap.ink_pixels().map(|p| p.1).max().unwrap_or(0);
+    let entry_height = cell.height * (DIGIT_LINES + 1);
+    let count = (y_max - origin.1) / entry_height + 1;
+
+    let entries = (0..count)
+        .map(|e| {
+            (0..digits)
+                .map(|d| {
+                    let mut segments = Segments::default();
+                    for (segment, row, column, c) in SEGMENT_CELLS {
+                        let (x, y, w, h) = stroke_rect(c, cell);
+                        let left = origin.0 + (d * 3 + column) * cell.width + x;
+                        let top = origin.1 + e * entry_height + row * cell.height + y;
+                        let ink = (top..top + h)
+                            .flat_map(|py| (left..left + w).map(move |px| (px, py)))
+                            .filter(|&(px, py)| bitmap.is_ink(px, py))
+                            .count();
+                        if ink * 2 >= w * h {
+                            segments = segments.union(segment);
+                        }
+                    }
+                    segments
+                })
+                .collect()
+        })
+        .collect();
+    ImageScan { origin, entries }
+}
+
+#[cfg(test)]
+mod tests_image {
+    use super::*;
+    use crate::recognizer::recognize_account_number;
+    use crate::renderer::render_account_number;
+    use crate::resolver::{Resolution, resolve_account_number};
+
+    const CELL: CellSize = CellSize {
+        width: 8,
+        height: 12,
+    };
+
+    #[test]
+    fn test_read_plain_pbm() {
+        let pbm = "P1\n# a comment\n3 2\n1 0 1\n010\n";
+        let image = read_pnm(pbm.as_bytes()).unwrap();
+        assert_eq!((3, 2), (image.width, image.height));
+        assert_eq!(vec![0, 255, 0, 255, 0, 255], image.pixels);
+    }
+
+    #[test]
+    fn test_read_binary_pbm() {
+        let mut pbm = b"P4\n10 2\n".to_vec();
+        pbm.extend([0b1000_0000, 0b0100_0000, 0b0000_0000, 0b1100_0000]);
+        let image = read_pnm(pbm.as_slice()).unwrap();
+        assert_eq!(0, image.get(0, 0));
+        assert_eq!(0, image.get(9, 0));
+        assert_eq!(255, image.get(1, 0));
+        assert_eq!(0, image.get(8, 1));
+        assert!(matches!(
+            read_pnm(&pbm[..9]),
+            Err(ImageError::TruncatedData)
+        ));
+    }
+
+    #[test]
+    fn test_oversized_header() {
+        let huge = format!("P5\n{} {} 255\n", usize::MAX, 2);
+        assert!(matches!(
+            read_pnm(huge.as_bytes()),
+            Err(ImageError::TruncatedData)
+        ));
+        assert!(matches!(
+            read_pnm(b"P2 100000 100000 255\n0 0 0".as_slice()),
+            Err(ImageError::TruncatedData)
+        ));
+        assert!(matches!(
+            read_pnm(b"P4 80 3\n\xff".as_slice()),
+            Err(ImageError::TruncatedData)
+        ));
+    }
+
+    #[test]
+    fn test_read_pgm() {
+        let image = read_pnm("P2 2 1 15 0 15".as_bytes()).unwrap();
+        assert_eq!(vec![0, 255], image.pixels);
+
+        let mut pgm = Vec::new();
+        write_pgm(&mut pgm, &image).unwrap();
+        assert_eq!(image, read_pnm(pgm.as_slice()).unwrap());
+        assert!(matches!(
+            read_pnm("GIF89a".as_bytes()),
+            Err(ImageError::UnsupportedFormat)
+        ));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_read_png() {
+        let image = render_image(&render_account_number("000000051").unwrap(), CELL, 2);
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, image.width as u32, image.height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&image.pixels)
+            .unwrap();
+
+        let decoded = read_png(data.as_slice()).unwrap();
+        assert_eq!(image, decoded);
+        let entries = scan_image(&decoded, CELL, 9).to_entries();
+        assert_eq!("000000051", recognize_account_number(&entries[0].text()));
+    }
+
+    #[test]
+    fn test_otsu_threshold() {
+        let mut image = GrayImage::new(4, 1);
+        image.pixels = vec![30, 40, 200, 220];
+        let threshold = image.otsu_threshold();
+        assert!((40..200).contains(&threshold));
+        assert_eq!(
+            vec![true, true, false, false],
+            (0..4)
+                .map(|x| image.binarize().is_ink(x, 0))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_scan_rendered_image() {
+        let text = format!(
+            "{}\n{}",
+            render_account_number("123456789").unwrap(),
+            render_account_number("490867715").unwrap()
+        );
+        let mut image = render_image(&text, CELL, 13);
+        // 紙の地色と薄いインクのむら
+        for (i, p) in image.pixels.iter_mut().enumerate() {
+            *p = if *p == 0 {
+                40 + (i % 7) as u8 * 10
+            } else {
+                230 - (i % 5) as u8 * 8
+            };
+        }
+
+        let scan = scan_image(&image, CELL, 9);
+        assert_eq!((13, 13), scan.origin);
+        let entries = scan.to_entries();
+        assert_eq!(2, entries.len());
+        assert_eq!("123456789", recognize_account_number(&entries[0].text()));
+        assert_eq!("490867715", recognize_account_number(&entries[1].text()));
+    }
+
+    #[test]
+    fn test_scan_leading_ones() {
+        // 最初の桁に左側の線がなくても格子の位置が分かる
+        let image = render_image(&render_account_number("111111111").unwrap(), CELL, 5);
+        let scan = scan_image(&image, CELL, 9);
+        assert_eq!(
+            "111111111",
+            recognize_account_number(&scan.to_entries()[0].text())
+        );
+    }
+
+    #[test]
+    fn test_scan_faded_segment() {
+        let text = render_account_number("123456789").unwrap();
+        let mut image = render_image(&text, CELL, 4);
+        // 1桁目の右下の線を消す
+        for y in 4 + 2 * CELL.height..4 + 3 * CELL.height {
+            for x in 4 + 2 * CELL.width..4 + 3 * CELL.width {
+                image.set(x, y, 255);
+            }
+        }
+        let entries = scan_image(&image, CELL, 9).to_entries();
+        assert_eq!("?23456789", recognize_account_number(&entries[0].text()));
+        assert_eq!(
+            Resolution::Corrected("123456789".to_string()),
+            resolve_account_number(&entries[0].text())
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_image_properties {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn arbitrary_bytes_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..200)) {
+            let _ = read_pnm(bytes.as_slice());
+        }
+
+        #[test]
+        fn huge_header_is_rejected(magic in "P[1245]", width in 1usize..1 << 40, height in 1usize..1 << 40) {
+            let pnm = format!("{magic} {width} {height} 255\n0 0 0");
+            prop_assert!(read_pnm(pnm.as_bytes()).is_err());
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use std::fmt;
+
+use crate::account_number::Glyph;
+use crate::segments::{SEGMENT_CELLS, Segments};
+
+/// Stands in for a mark that is not a segment, where the kata's glyph would
+/// otherwise read the mark as its own segment.
+const NOISE: char = '*';
+
+/// How the entries of a scanner file are drawn: the size of a glyph, the
+/// characters of its segments and whether a blank line follows each entry.
+///
+/// The horizontal segments fill the top, middle and bottom rows between the
+/// first and last columns, and the vertical segments fill those columns above
+/// and below the middle row, as in the kata's 3x3 glyphs. An `8`:
+///
+/// ```text
+///  __      ##
+/// |__|    ####
+/// |  |    #  #
+/// |__|    ####
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub glyph_width: usize,
+    pub glyph_height: usize,
+    /// The character of the `a`, `g` and `d` segments.
+    pub horizontal: char,
+    /// The character of the `b`, `c`, `e` and `f` segments.
+    pub vertical: char,
+    /// A blank line follows the glyph lines of every entry.
+    pub separator: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutError {
+    /// A glyph needs at least three columns and three rows to draw seven segments.
+    GlyphTooSmall { width: usize, height: usize },
+    /// Segments must be drawn with a visible character.
+    InvalidSegmentCharacter(char),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::GlyphTooSmall { width, height } => {
+                write!(f, "glyphs of {width}x{height} are smaller than 3x3")
+            }
+            LayoutError::InvalidSegmentCharacter(c) => {
+                write!(f, "{c:?} cannot be used to draw segments")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl Layout {
+    /// Glyphs of `width` by `height` characters, drawn with pipes and underscores.
+    pub fn new(width: usize, height: usize) -> Result<Self, LayoutError> {
+        if width < 3 || height < 3 {
+            return Err(LayoutError::GlyphTooSmall { width, height });
+        }
+        Ok(Self {
+            glyph_width: width,
+            glyph_height: height,
+            ..Self::kata()
+        })
+    }
+
+    /// The 3x3 glyphs of pipes and underscores, and the blank line, of the kata.
+    pub fn kata() -> Self {
+        Self {
+            glyph_width: 3,
+            glyph_height: 3,
+            horizontal: '_',
+            vertical: '|',
+            separator: true,
+        }
+    }
+
+    pub fn with_segment_chars(
+        mut self,
+        horizontal: char,
+        vertical: char,
+    ) -> Result<Self, LayoutError> {
+        for c in [horizontal, vertical] {
+            if c.is_whitespace() || c.is_control() {
+                return Err(LayoutError::InvalidSegmentCharacter(c));
+            }
+        }
+        self.horizontal = horizontal;
+        self.vertical = vertical;
+        Ok(self)
+    }
+
+    pub fn with_separator(mut self, separator: bool) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// The width of an entry line holding `digits` glyphs.
+    pub fn width(&self, digits: usize) -> usize {
+        digits * self.glyph_width
+    }
+
+    /// The lines an entry takes, the separator included.
+    pub fn entry_lines(&self) -> usize {
+        self.glyph_height + usize::from(self.separator)
+    }
+
+    fn middle_row(&self) -> usize {
+        (self.glyph_height - 1) / 2
+    }
+
+    /// The cell of the kata's 3x3 glyph that a cell of this layout stands for.
+    fn kata_cell(&self, row: usize, column: usize) -> (usize, usize) {
+        let kata_row = match row {
+            0 => 0,
+            r if r <= self.middle_row() => 1,
+            _ => 2,
+        };
+        let kata_column = match column {
+            0 => 0,
+            c if c + 1 == self.glyph_width => 2,
+            _ => 1,
+        };
+        (kata_row, kata_column)
+    }
+
+    /// The segment drawn at a cell, and with which character.
+    fn segment_at(&self, row: usize, column: usize) -> Option<(Segments, char)> {
+        let (kata_row, kata_column) = self.kata_cell(row, column);
+        if kata_column == 1 {
+            let on_bar = row == 0 || row == self.middle_row() || row + 1 == self.glyph_height;
+            if !on_bar {
+                return None;
+            }
+        }
+        SEGMENT_CELLS
+            .iter()
+            .find(|(_, r, c, _)| *r == kata_row && *c == kata_column)
+            .map(|(segment, ..)| {
+                let c = if kata_column == 1 {
+                    self.horizontal
+                } else {
+                    self.vertical
+                };
+                (*segment, c)
+            })
+    }
+
+    /// Cuts out the glyph of the `index`-th digit and redraws it as the kata's
+    /// 3x3 glyph, so it can be read by `GlyphTable` and `segments::read_glyph`.
+    /// Cells past the end of a short line, or of missing lines, read as spaces.
+    ///
+    /// A segment is drawn when any of its cells is. Marks that are not segments
+    /// are kept as noise, but several of them in the cells standing for one cell
+    /// of the kata's glyph count once. A corner drawn with the character of a
+    /// segment it touches is part of that segment when the segment is drawn
+    /// elsewhere, as in block fonts with filled corners.
+    pub fn cut(&self, text: &str, index: usize) -> Glyph {
+        let mut glyph = [[' '; 3]; 3];
+        let mut corners = Vec::new();
+        for (row, line) in text.lines().take(self.glyph_height).enumerate() {
+            let cells = line
+                .chars()
+                .skip(index * self.glyph_width)
+                .take(self.glyph_width);
+            for (column, c) in cells.enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                if self.is_corner(row, column) {
+                    corners.push((row, column, c));
+                    continue;
+                }
+                self.mark(&mut glyph, row, column, c);
+            }
+        }
+        // 角はほかのセルで線が引かれているかが分かってから読む
+        for (row, column, c) in corners {
+            let (kata_row, kata_column) = self.kata_cell(row, column);
+            let touches_horizontal = c == self.horizontal && glyph[kata_row][1] == '_';
+            let vertical_row = if kata_row == 0 { 1 } else { kata_row };
+            let touches_vertical = c == self.vertical && glyph[vertical_row][kata_column] == '|';
+            if !touches_horizontal && !touches_vertical {
+                self.mark(&mut glyph, row, column, c);
+            }
+        }
+        glyph
+    }
+
+    fn is_corner(&self, row: usize, column: usize) -> bool {
+        (row == 0 || row + 1 == self.glyph_height)
+            && (column == 0 || column + 1 == self.glyph_width)
+    }
+
+    /// Redraws the mark `c` at a cell in the kata's glyph, as a segment or noise.
+    fn mark(&self, glyph: &mut Glyph, row: usize, column: usize, c: char) {
+        let (kata_row, kata_column) = self.kata_cell(row, column);
+        let kata_char = SEGMENT_CELLS
+            .iter()
+            .find(|(_, r, k, _)| *r == kata_row && *k == kata_column)
+            .map(|(.., c)| *c);
+        let cell = &mut glyph[kata_row][kata_column];
+        match self.segment_at(row, column) {
+            Some((_, drawn)) if c == drawn => *cell = kata_char.unwrap_or(c),
+            _ if *cell == ' ' => *cell = if Some(c) == kata_char { NOISE } else { c },
+            _ => {}
+        }
+    }
+
+    /// Draws `segments` as `glyph_height` lines of `glyph_width` characters.
+    pub fn draw(&self, segments: Segments) -> Vec<String> {
+        (0..self.glyph_height)
+            .map(|row| {
+                (0..self.glyph_width)
+                    .map(|column| match self.segment_at(row, column) {
+                        Some((segment, c)) if segments.contains(segment) => c,
+                        _ => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::kata()
+    }
+}
+
+#[cfg(test)]
+mod tests_layout {
+    use super::*;
+    use crate::checksum::{AccountFormat, Mod11};
+    use crate::glyph_table::GlyphTable;
+    use crate::parser::parse_entries_normalized;
+    use crate::recognizer::{EIGHT_PATTERN, get_digit_pattern};
+    use crate::renderer::write_scanner_file_with;
+    use crate::report::{EntryReport, ReportStatus, report_entry};
+    use crate::search::SearchOptions;
+    use crate::segments::DIGIT_SEGMENTS;
+
+    #[test]
+    fn test_kata_layout_draws_kata_glyphs() {
+        let layout = Layout::kata();
+        for (d, segments) in DIGIT_SEGMENTS.iter().enumerate() {
+            let pattern = get_digit_pattern((b'0' + d as u8) as char);
+            let drawn = layout.draw(*segments);
+            let expected: Vec<String> = pattern.iter().map(|row| row.iter().collect()).collect();
+            assert_eq!(expected, drawn);
+            assert_eq!(pattern, layout.cut(&drawn.join("\n"), 0));
+        }
+        assert_eq!(4, layout.entry_lines());
+        assert_eq!(27, layout.width(9));
+    }
+
+    #[test]
+    fn test_cut_keeps_noise_of_kata_glyphs() {
+        let glyph = Layout::kata().cut("_|_\n|x|\n ~|", 0);
+        assert_eq!([['_', '|', '_'], ['|', 'x', '|'], [' ', '~', '|']], glyph);
+    }
+
+    #[test]
+    fn test_four_by_four() {
+        let layout = Layout::new(4, 4).unwrap();
+        assert_eq!(
+            vec![" __ ", "|__|", "|  |", "|__|"],
+            layout.draw(DIGIT_SEGMENTS[8])
+        );
+        assert_eq!(
+            vec!["    ", "   |", "   |", "   |"],
+            layout.draw(DIGIT_SEGMENTS[1])
+        );
+        assert_eq!(EIGHT_PATTERN, layout.cut(" __ \n|__|\n|  |\n|__|", 0));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let layout = Layout::new(4, 4)
+            .unwrap()
+            .with_segment_chars('#', '#')
+            .unwrap()
+            .with_separator(false);
+        let text = [" ## ", "####", "#  #", "####"].join("\n");
+        assert_eq!(text, layout.draw(DIGIT_SEGMENTS[8]).join("\n"));
+        assert_eq!(EIGHT_PATTERN, layout.cut(&text, 0));
+        assert_eq!(4, layout.entry_lines());
+
+        // 線につながらない角の # や、別の文字で描かれた線は線ではない
+        let glyph = layout.cut("#  #\n   #\n _ #\n   #", 0);
+        assert_eq!([['#', ' ', ' '], [' ', ' ', '|'], [' ', NOISE, '|']], glyph);
+    }
+
+    #[test]
+    fn test_blocks_with_filled_corners() {
+        use crate::recognizer::recognize_entry;
+        use crate::scoring::score_entry;
+
+        let layout = Layout::new(4, 4)
+            .unwrap()
+            .with_segment_chars('#', '#')
+            .unwrap()
+            .with_separator(false);
+        assert_eq!(EIGHT_PATTERN, layout.cut("####\n####\n#  #\n####", 0));
+
+        // 横線が引かれている行は角も埋める
+        let table = GlyphTable::hex();
+        let number = "0123456789AbCdEF";
+        let mut lines = vec![String::new(); 4];
+        for c in number.chars() {
+            let mut glyph = layout.draw(table.segments(c).unwrap());
+            for row in [0, 3] {
+                if &glyph[row][1..3] == "##" {
+                    glyph[row] = "####".to_string();
+                }
+            }
+            for (line, row) in lines.iter_mut().zip(glyph) {
+                line.push_str(&row);
+            }
+        }
+        let text = lines.join("\n");
+        assert!(text.starts_with("####    ########"));
+
+        let format = AccountFormat::new(number.len(), Box::new(Mod11))
+            .with_layout(layout)
+            .with_table(table);
+        assert_eq!(number, recognize_entry(&text, &format));
+        assert_eq!(number, score_entry(&text, &format).exact());
+    }
+
+    #[test]
+    fn test_scanner_file_in_layout() {
+        let layout = Layout::new(4, 4)
+            .unwrap()
+            .with_segment_chars('#', '#')
+            .unwrap()
+            .with_separator(false);
+        let format = AccountFormat::kata().with_layout(layout);
+        let mut file = Vec::new();
+        write_scanner_file_with(
+            &mut file,
+            ["457508000", "664371495"],
+            &GlyphTable::digits(),
+            &layout,
+        )
+        .unwrap();
+        let file = String::from_utf8(file).unwrap();
+        assert_eq!(8, file.lines().count());
+        // 右に1列ずれた行も読める
+        let drifted: String = file.lines().map(|l| format!(" {l}\n")).collect();
+
+        let search = SearchOptions::default();
+        let reports: Vec<EntryReport> =
+            parse_entries_normalized(drifted.as_bytes(), format.width())
+                .with_layout(layout)
+                .map(|entry| report_entry(&entry.unwrap(), None, &format, Some(&search)))
+                .collect();
+        assert_eq!(2, reports.len());
+        assert_eq!(ReportStatus::Ok, reports[0].status);
+        assert_eq!("457508000", reports[0].account_number);
+        assert_eq!(5, reports[1].line_number);
+        assert_eq!(ReportStatus::Corrected, reports[1].status);
+        assert_eq!("664371485", reports[1].account_number);
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        assert_eq!(
+            Err(LayoutError::GlyphTooSmall {
+                width: 2,
+                height: 4
+            }),
+            Layout::new(2, 4)
+        );
+        assert_eq!(
+            Err(LayoutError::InvalidSegmentCharacter(' ')),
+            Layout::kata().with_segment_chars(' ', '|')
+        );
+    }
+}
@@ -1,3 +1,23 @@
+pub mod account_number;
+pub mod batch;
+pub mod checksum;
+pub mod evaluation;
+pub mod glyph_table;
+pub mod image;
+pub mod layout;
+pub mod noise;
+pub mod normalize;
+pub mod output;
+pub mod parser;
+pub mod printer;
 pub mod recognizer;
+pub mod registry;
+pub mod renderer;
+pub mod report;
+pub mod resolver;
+pub mod review;
+pub mod scoring;
+pub mod search;
+pub mod segments;
+pub mod stream;
 pub mod validator;
-pub mod printer;
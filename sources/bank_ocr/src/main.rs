@@ -0,0 +1,648 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use bank_ocr::batch::{
+    BatchOptions, SUMMARY_CSV, SUMMARY_JSON, process_directory, write_summary_csv,
+    write_summary_json,
+};
+use bank_ocr::checksum::{AccountFormat, checksum_by_name};
+use bank_ocr::evaluation::{Evaluation, read_expected};
+use bank_ocr::glyph_table::GlyphTable;
+use bank_ocr::image::{CellSize, read_image, scan_image};
+use bank_ocr::layout::Layout;
+use bank_ocr::output::OutputFormat;
+use bank_ocr::parser::{Entry, ParseError, parse_entries_normalized, parse_entries_with_width};
+use bank_ocr::registry::AccountRegistry;
+use bank_ocr::report::Summary;
+use bank_ocr::review::{ReviewQueue, write_audit_csv};
+use bank_ocr::search::{MAX_EDITS, SearchOptions};
+use bank_ocr::stream::Reports;
+
+const USAGE: &str = "\
+Usage: bank_ocr [OPTIONS] [INPUT]
+       bank_ocr --batch -o OUTPUT_DIR [OPTIONS] INPUT_DIR
+       bank_ocr --image WxH [OPTIONS] IMAGE
+
+Reads a scanner file (or stdin when INPUT is omitted or `-`) and writes one
+account number per line with ERR, ILL or AMB status.
+
+With --batch, every file in INPUT_DIR is processed concurrently and its report
+is written to OUTPUT_DIR/<file name>.report, along with summary.csv and
+summary.json holding the counts per file.
+
+With --review, AMB and ILL entries are also written to FILE for a reviewer, who
+fills in each `decision:` line with the account number or `reject`. Running
+again with --decisions FILE merges those decisions into the report, where
+accepted numbers have the REVIEWED status, and --audit writes the decisions
+made as CSV.
+
+With --evaluate, the entries are compared with the account numbers expected in
+FILE, one per line, and the accuracy of recognition and correction is written
+instead of the report.
+
+With --image, INPUT is a PBM or PGM image (or PNG, when built with the `png`
+feature) of scanner entries, each character cell W by H pixels.
+
+Options:
+  -o, --output FILE   write the report to FILE instead of stdout
+      --digits N      number of digits of an account number (default: 9)
+      --checksum NAME mod11, luhn, mod97-10 or verhoeff (default: mod11)
+      --glyph WxH     glyphs of W columns by H lines (default: 3x3)
+      --segment-chars CHARS
+                      the characters of the horizontal and vertical segments,
+                      or one character for both (default: _|)
+      --glyphs TABLE  digits, hex, dash or a glyph table file (default: digits)
+      --no-separator  entries are not followed by a blank line
+      --max-edits N   pipes or underscores a correction may change, at most 3
+                      (default: 1)
+      --no-correct    do not try to correct ERR and ILL numbers
+      --strict        reject lines that are not exactly as wide as an entry,
+                      instead of padding, realigning and expanding tabs
+      --format NAME   text, jsonl or csv (default: text)
+      --summary       print the number of entries per status to stderr
+      --batch         process a directory of scanner files
+      --jobs N        number of files processed at the same time in batch mode
+                      (default: number of CPUs)
+      --image WxH     read INPUT as an image with cells of W by H pixels
+      --review FILE   write AMB and ILL entries to FILE for manual review
+      --decisions FILE
+                      merge the decisions of a reviewed FILE into the report
+      --audit FILE    write the merged decisions to FILE as CSV
+      --registry FILE keep only the alternatives listed in FILE, one account
+                      number per line, and flag numbers that are not listed
+      --evaluate FILE report accuracy against the numbers expected in FILE
+  -h, --help          print this help";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    input: Option<String>,
+    output: Option<String>,
+    digits: usize,
+    checksum: String,
+    layout: Layout,
+    glyphs: Option<String>,
+    max_edits: u32,
+    correct: bool,
+    normalize: bool,
+    output_format: OutputFormat,
+    summary: bool,
+    batch: bool,
+    jobs: Option<usize>,
+    image: Option<CellSize>,
+    review: Option<String>,
+    decisions: Option<String>,
+    audit: Option<String>,
+    registry: Option<String>,
+    evaluate: Option<String>,
+}
+
+impl Options {
+    fn format(&self) -> Result<AccountFormat, String> {
+        let checksum = checksum_by_name(&self.checksum)
+            .ok_or(format!("unknown checksum: {}", self.checksum))?;
+        let format = AccountFormat::new(self.digits, checksum).with_layout(self.layout);
+        let Some(glyphs) = &self.glyphs else {
+            return Ok(format);
+        };
+        let table = match GlyphTable::preset(glyphs) {
+            Some(table) => table,
+            None => GlyphTable::load(glyphs).map_err(|e| format!("{glyphs}: {e}"))?,
+        };
+        Ok(format.with_table(table))
+    }
+
+    fn registry(&self) -> Result<Option<AccountRegistry>, Box<dyn Error>> {
+        match &self.registry {
+            Some(path) => Ok(Some(AccountRegistry::load(path, self.digits)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn search(&self) -> Option<SearchOptions> {
+        self.correct.then_some(SearchOptions {
+            max_edits: self.max_edits,
+            ..SearchOptions::default()
+        })
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        input: None,
+        output: None,
+        digits: 9,
+        checksum: "mod11".to_string(),
+        layout: Layout::kata(),
+        glyphs: None,
+        max_edits: 1,
+        correct: true,
+        normalize: true,
+        output_format: OutputFormat::Text,
+        summary: false,
+        batch: false,
+        jobs: None,
+        image: None,
+        review: None,
+        decisions: None,
+        audit: None,
+        registry: None,
+        evaluate: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-o" | "--output" => {
+                let output = args.next().ok_or(format!("{arg} requires a file name"))?;
+                options.output = Some(output);
+            }
+            "--digits" => {
+                let digits = args.next().ok_or(format!("{arg} requires a number"))?;
+                options.digits = match digits.parse() {
+                    Ok(digits) if digits > 0 => digits,
+                    _ => return Err(format!("invalid number of digits: {digits}")),
+                };
+            }
+            "--checksum" => {
+                let checksum = args.next().ok_or(format!("{arg} requires a name"))?;
+                options.checksum = checksum;
+            }
+            "--glyph" => {
+                let size = args.next().ok_or(format!("{arg} requires a glyph size"))?;
+                let glyph = match size.split_once('x').map(|(w, h)| (w.parse(), h.parse())) {
+                    Some((Ok(width), Ok(height))) => {
+                        Layout::new(width, height).map_err(|e| e.to_string())?
+                    }
+                    _ => return Err(format!("invalid glyph size: {size}")),
+                };
+                options.layout.glyph_width = glyph.glyph_width;
+                options.layout.glyph_height = glyph.glyph_height;
+            }
+            "--segment-chars" => {
+                let chars = args.next().ok_or(format!("{arg} requires characters"))?;
+                let (horizontal, vertical) = match chars.chars().collect::<Vec<char>>()[..] {
+                    [c] => (c, c),
+                    [horizontal, vertical] => (horizontal, vertical),
+                    _ => return Err(format!("invalid segment characters: {chars}")),
+                };
+                options.layout = options
+                    .layout
+                    .with_segment_chars(horizontal, vertical)
+                    .map_err(|e| e.to_string())?;
+            }
+            "--no-separator" => options.layout = options.layout.with_separator(false),
+            "--glyphs" => {
+                let glyphs = args.next().ok_or(format!("{arg} requires a table"))?;
+                options.glyphs = Some(glyphs);
+            }
+            "--max-edits" => {
+                let max_edits = args.next().ok_or(format!("{arg} requires a number"))?;
+                options.max_edits = match max_edits.parse() {
+                    Ok(n) if n <= MAX_EDITS => n,
+                    _ => {
+                        return Err(format!(
+                            "invalid number of edits: {max_edits} (at most {MAX_EDITS})"
+                        ));
+                    }
+                };
+            }
+            "--no-correct" => options.correct = false,
+            "--strict" => options.normalize = false,
+            "--format" => {
+                let name = args.next().ok_or(format!("{arg} requires a name"))?;
+                options.output_format =
+                    OutputFormat::by_name(&name).ok_or(format!("unknown output format: {name}"))?;
+            }
+            "--summary" => options.summary = true,
+            "--batch" => options.batch = true,
+            "--jobs" => {
+                let jobs = args.next().ok_or(format!("{arg} requires a number"))?;
+                options.jobs = match jobs.parse() {
+                    Ok(jobs) if jobs > 0 => Some(jobs),
+                    _ => return Err(format!("invalid number of jobs: {jobs}")),
+                };
+            }
+            "--image" => {
+                let size = args.next().ok_or(format!("{arg} requires a cell size"))?;
+                options.image = match size.split_once('x').map(|(w, h)| (w.parse(), h.parse())) {
+                    Some((Ok(width), Ok(height))) if width > 0 && height > 0 => {
+                        Some(CellSize { width, height })
+                    }
+                    _ => return Err(format!("invalid cell size: {size}")),
+                };
+            }
+            "--review" => {
+                let review = args.next().ok_or(format!("{arg} requires a file name"))?;
+                options.review = Some(review);
+            }
+            "--decisions" => {
+                let decisions = args.next().ok_or(format!("{arg} requires a file name"))?;
+                options.decisions = Some(decisions);
+            }
+            "--audit" => {
+                let audit = args.next().ok_or(format!("{arg} requires a file name"))?;
+                options.audit = Some(audit);
+            }
+            "--registry" => {
+                let registry = args.next().ok_or(format!("{arg} requires a file name"))?;
+                options.registry = Some(registry);
+            }
+            "--evaluate" => {
+                let expected = args.next().ok_or(format!("{arg} requires a file name"))?;
+                options.evaluate = Some(expected);
+            }
+            "-" => options.input = None,
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {arg}")),
+            _ if options.input.is_some() => return Err(format!("unexpected argument: {arg}")),
+            _ => options.input = Some(arg),
+        }
+    }
+    if options.batch && (options.input.is_none() || options.output.is_none()) {
+        return Err("--batch requires an input and an output directory".to_string());
+    }
+    if options.image.is_some() && (options.batch || options.input.is_none()) {
+        return Err("--image requires an input file and no --batch".to_string());
+    }
+    if options.image.is_some() && options.layout != Layout::kata() {
+        return Err(
+            "--glyph, --segment-chars and --no-separator cannot be used with --image".to_string(),
+        );
+    }
+    if options.audit.is_some() && options.decisions.is_none() {
+        return Err("--audit requires --decisions".to_string());
+    }
+    if options.batch
+        && (options.review.is_some() || options.decisions.is_some() || options.evaluate.is_some())
+    {
+        return Err("--review, --decisions and --evaluate cannot be used with --batch".to_string());
+    }
+    Ok(Some(options))
+}
+
+fn run(options: &Options) -> Result<Summary, Box<dyn Error>> {
+    let format = options.format()?;
+    let registry = options.registry()?;
+    let mut output: Box<dyn Write> = match &options.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    let expected = match &options.evaluate {
+        Some(path) => Some(read_expected(
+            BufReader::new(File::open(path)?),
+            format.length,
+        )?),
+        None => None,
+    };
+    if let Some(header) = options.output_format.header()
+        && expected.is_none()
+    {
+        writeln!(output, "{header}")?;
+    }
+    let reviewed = match &options.decisions {
+        Some(path) => Some(ReviewQueue::from_reader(BufReader::new(File::open(path)?))?),
+        None => None,
+    };
+    let mut queue = ReviewQueue::new();
+    // 判定をマージするときや評価するときは全エントリを読んでから書き出す
+    let hold = reviewed.is_some() || expected.is_some();
+    let mut held = Vec::new();
+    let mut summary = Summary::default();
+
+    let entries: Box<dyn Iterator<Item = Result<Entry, ParseError>>> =
+        if let (Some(cell), Some(path)) = (options.image, &options.input) {
+            let scan = scan_image(&read_image(path)?, cell, format.length);
+            Box::new(scan.to_entries().into_iter().map(Ok))
+        } else {
+            let input: Box<dyn BufRead> = match &options.input {
+                Some(path) => Box::new(BufReader::new(File::open(path)?)),
+                None => Box::new(io::stdin().lock()),
+            };
+            let entries = if options.normalize {
+                parse_entries_normalized(input, format.width())
+            } else {
+                parse_entries_with_width(input, format.width())
+            };
+            Box::new(
+                entries
+                    .with_layout(format.layout)
+                    .with_table(format.table.clone()),
+            )
+        };
+    let reports = Reports::new(entries, &format)
+        .with_search(options.search())
+        .with_registry(registry.as_ref())
+        .with_source(options.input.as_deref());
+    for report in reports {
+        let report = match report {
+            Ok(report) => report,
+            Err(e @ ParseError::Io(_)) => return Err(e.into()),
+            // 壊れたエントリは飛ばして、次のエントリから続ける
+            Err(e) => {
+                eprintln!("bank_ocr: {e}");
+                summary.broken += 1;
+                continue;
+            }
+        };
+        queue.push(&report);
+        if hold {
+            held.push(report);
+            continue;
+        }
+        summary.record(report.status);
+        writeln!(output, "{}", options.output_format.format(&report))?;
+    }
+
+    if let Some((reviewed, decisions)) = &reviewed {
+        let audit = reviewed.merge(&mut held, decisions, &format)?;
+        if let Some(path) = &options.audit {
+            let mut file = BufWriter::new(File::create(path)?);
+            write_audit_csv(&mut file, &audit)?;
+            file.flush()?;
+        }
+    }
+    for report in &held {
+        summary.record(report.status);
+    }
+    match &expected {
+        Some(expected) => Evaluation::evaluate(held, expected)?.write_to(&mut output)?,
+        None => {
+            for report in &held {
+                writeln!(output, "{}", options.output_format.format(report))?;
+            }
+        }
+    }
+    if let Some(path) = &options.review {
+        let mut file = BufWriter::new(File::create(path)?);
+        queue.write_to(&mut file)?;
+        file.flush()?;
+    }
+    output.flush()?;
+    Ok(summary)
+}
+
+/// Processes a directory; corrupted files are reported to stderr and counted as failed.
+fn run_batch(options: &Options) -> Result<(Summary, usize), Box<dyn Error>> {
+    let format = options.format()?;
+    let mut batch = BatchOptions {
+        search: options.search(),
+        output: options.output_format,
+        normalize: options.normalize,
+        registry: options.registry()?,
+        ..BatchOptions::default()
+    };
+    if let Some(jobs) = options.jobs {
+        batch.workers = jobs;
+    }
+    let (Some(input), Some(output)) = (&options.input, &options.output) else {
+        unreachable!("parse_args checks --batch has both directories");
+    };
+    let output = Path::new(output);
+    let results = process_directory(Path::new(input), output, &format, &batch)?;
+
+    let mut csv = BufWriter::new(File::create(output.join(SUMMARY_CSV))?);
+    write_summary_csv(&mut csv, &results)?;
+    csv.flush()?;
+    let mut json = BufWriter::new(File::create(output.join(SUMMARY_JSON))?);
+    write_summary_json(&mut json, &results)?;
+    json.flush()?;
+
+    let mut summary = Summary::default();
+    let mut failed = 0;
+    for result in &results {
+        summary.add(&result.summary);
+        if let Some(error) = &result.error {
+            eprintln!("bank_ocr: {}: {error}", result.input.display());
+            failed += 1;
+        }
+    }
+    Ok((summary, failed))
+}
+
+fn print_summary(summary: &Summary) {
+    eprintln!("total: {}", summary.total());
+    eprintln!(
+        "OK: {} (corrected: {})",
+        summary.ok + summary.corrected,
+        summary.corrected
+    );
+    eprintln!("ERR: {}", summary.err);
+    eprintln!("ILL: {}", summary.ill);
+    eprintln!("AMB: {}", summary.amb);
+    eprintln!("REVIEWED: {}", summary.reviewed);
+    eprintln!("broken: {}", summary.broken);
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("bank_ocr: {message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = if options.batch {
+        run_batch(&options)
+    } else {
+        run(&options).map(|summary| (summary, 0))
+    };
+    match result {
+        Ok((summary, failed)) => {
+            if options.summary {
+                print_summary(&summary);
+            }
+            if failed > 0 {
+                eprintln!("bank_ocr: {failed} files could not be processed");
+                return ExitCode::FAILURE;
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("bank_ocr: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_main {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse_args(args(&[
+                "in.txt",
+                "-o",
+                "out.txt",
+                "--digits",
+                "11",
+                "--checksum",
+                "luhn",
+                "--max-edits",
+                "2",
+                "--no-correct",
+                "--strict",
+                "--format",
+                "csv",
+                "--summary"
+            ])),
+            Ok(Some(Options {
+                input: Some("in.txt".to_string()),
+                output: Some("out.txt".to_string()),
+                digits: 11,
+                checksum: "luhn".to_string(),
+                layout: Layout::kata(),
+                glyphs: None,
+                max_edits: 2,
+                correct: false,
+                normalize: false,
+                output_format: OutputFormat::Csv,
+                summary: true,
+                batch: false,
+                jobs: None,
+                image: None,
+                review: None,
+                decisions: None,
+                audit: None,
+                registry: None,
+                evaluate: None,
+            }))
+        );
+        let batch = parse_args(args(&["--batch", "--jobs", "4", "-o", "out", "in"]))
+            .unwrap()
+            .unwrap();
+        assert!(batch.batch);
+        assert_eq!(Some(4), batch.jobs);
+        assert!(parse_args(args(&["--batch", "in"])).is_err());
+        assert!(parse_args(args(&["--jobs", "0"])).is_err());
+        assert!(parse_args(args(&["--max-edits", "3", "in"])).is_ok());
+        assert!(parse_args(args(&["--max-edits", "4", "in"])).is_err());
+        assert!(parse_args(args(&["--format", "xml"])).is_err());
+        let image = parse_args(args(&["--image", "8x12", "scan.pgm"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Some(CellSize {
+                width: 8,
+                height: 12
+            }),
+            image.image
+        );
+        assert!(parse_args(args(&["--image", "8x0", "scan.pgm"])).is_err());
+        assert!(parse_args(args(&["--image", "8x12"])).is_err());
+        assert!(parse_args(args(&["--audit", "audit.csv"])).is_err());
+        let blocks = parse_args(args(&[
+            "--segment-chars",
+            "#",
+            "--glyph",
+            "4x5",
+            "--no-separator",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            Layout::new(4, 5)
+                .unwrap()
+                .with_segment_chars('#', '#')
+                .unwrap()
+                .with_separator(false),
+            blocks.layout
+        );
+        assert_eq!(36, blocks.format().unwrap().width());
+        assert!(parse_args(args(&["--glyph", "2x3"])).is_err());
+        assert!(parse_args(args(&["--segment-chars", "_|-"])).is_err());
+        assert!(parse_args(args(&["--glyph", "4x4", "--image", "8x12", "scan.pgm"])).is_err());
+        assert!(parse_args(args(&["--batch", "--evaluate", "e", "-o", "out", "in"])).is_err());
+        assert!(parse_args(args(&["--batch", "--review", "r", "-o", "out", "in"])).is_err());
+        assert_eq!(parse_args(args(&["--help"])), Ok(None));
+        assert!(parse_args(args(&["-o"])).is_err());
+        assert!(parse_args(args(&["--unknown"])).is_err());
+        assert!(parse_args(args(&["a.txt", "b.txt"])).is_err());
+        assert!(parse_args(args(&["--digits", "0"])).is_err());
+        assert!(
+            parse_args(args(&["--checksum", "crc32"]))
+                .unwrap()
+                .unwrap()
+                .format()
+                .is_err()
+        );
+
+        let hex = parse_args(args(&["--glyphs", "hex"])).unwrap().unwrap();
+        assert_eq!(GlyphTable::hex(), hex.format().unwrap().table);
+        assert_eq!(
+            GlyphTable::digits(),
+            parse_args(args(&[]))
+                .unwrap()
+                .unwrap()
+                .format()
+                .unwrap()
+                .table
+        );
+        let missing = parse_args(args(&["--glyphs", "no-such-table.txt"]))
+            .unwrap()
+            .unwrap();
+        assert!(missing.format().is_err());
+    }
+
+    #[test]
+    fn test_glyph_table_file() {
+        let path = std::env::temp_dir().join(format!("bank_ocr_glyphs_{}", std::process::id()));
+        std::fs::write(&path, " A\n _\n|_|\n| |\n").unwrap();
+        let options = parse_args(args(&["--glyphs", path.to_str().unwrap()]))
+            .unwrap()
+            .unwrap();
+        let table = options.format().unwrap().table;
+        assert!(table.segments('A').is_some());
+        assert!(table.segments('0').is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_run_resumes_after_broken_entry() {
+        use bank_ocr::renderer::render_account_number;
+
+        let dir = std::env::temp_dir().join(format!("bank_ocr_run_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("scan.txt");
+        let output = dir.join("scan.report");
+        // 2件目のエントリの2行目が短い
+        let broken = render_account_number("664371495")
+            .unwrap()
+            .replacen(' ', "", 3);
+        let entries = ["457508000", "", "123456789"].map(|n| match n {
+            "" => broken.clone(),
+            n => render_account_number(n).unwrap(),
+        });
+        std::fs::write(&input, entries.join("\n") + "\n").unwrap();
+
+        let options = parse_args(args(&[
+            input.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "--strict",
+        ]))
+        .unwrap()
+        .unwrap();
+        let summary = run(&options).unwrap();
+        assert_eq!((2, 1), (summary.ok, summary.broken));
+        assert_eq!(
+            "457508000\n123456789\n",
+            std::fs::read_to_string(&output).unwrap()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
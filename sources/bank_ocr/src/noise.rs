@@ -0,0 +1,351 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::glyph_table::GlyphTable;
+use crate::renderer::render_with_glyphs;
+use crate::segments::SEGMENT_CELLS;
+
+const STRAY_CHARACTERS: [char; 5] = ['.', '\'', ',', '`', '-'];
+
+/// The kind of damage applied to one entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Noise {
+    Clean,
+    /// One pipe or underscore is missing (User Story 4).
+    DropSegment,
+    /// One pipe or underscore is added (User Story 4).
+    AddSegment,
+    /// Several pipes or underscores are dropped or added, possibly in different digits.
+    MultiSegment(usize),
+    /// The whole entry is shifted one column to the right, so its lines are one
+    /// column wider.
+    ShiftColumns,
+    /// A character that is neither a pipe nor an underscore appears somewhere in the entry.
+    StrayCharacter,
+    /// Trailing spaces of one line are trimmed.
+    TruncateLine,
+}
+
+impl fmt::Display for Noise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Noise::Clean => write!(f, "clean"),
+            Noise::DropSegment => write!(f, "drop-segment"),
+            Noise::AddSegment => write!(f, "add-segment"),
+            Noise::MultiSegment(n) => write!(f, "multi-segment-{n}"),
+            Noise::ShiftColumns => write!(f, "shift-columns"),
+            Noise::StrayCharacter => write!(f, "stray-character"),
+            Noise::TruncateLine => write!(f, "truncate-line"),
+        }
+    }
+}
+
+/// A small SplitMix64 generator, so fixtures are reproducible from a seed.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// The expected result for one generated entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnswerKeyEntry {
+    pub account_number: String,
+    pub noise: Noise,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    pub scanner_file: String,
+    pub answer_key: Vec<AnswerKeyEntry>,
+}
+
+impl Fixture {
+    /// Writes one `<account number> <noise>` line per entry.
+    pub fn write_answer_key<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for entry in self.answer_key.iter() {
+            writeln!(writer, "{} {}", entry.account_number, entry.noise)?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates scanner files of valid account numbers with controlled noise.
+pub struct NoiseGenerator {
+    rng: Rng,
+    mix: Vec<(Noise, u32)>,
+    table: GlyphTable,
+}
+
+impl NoiseGenerator {
+    /// Creates a generator that produces clean entries until noise is added with `with_noise`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            mix: Vec::new(),
+            table: GlyphTable::digits(),
+        }
+    }
+
+    /// Adds `noise` to the mix; each entry picks its noise in proportion to the weights.
+    pub fn with_noise(mut self, noise: Noise, weight: u32) -> Self {
+        self.mix.push((noise, weight));
+        self
+    }
+
+    pub fn generate(&mut self, count: usize) -> Fixture {
+        let mut scanner_file = String::new();
+        let mut answer_key = Vec::with_capacity(count);
+        for _ in 0..count {
+            let account_number = self.valid_account_number();
+            let noise = self.pick_noise();
+            scanner_file.push_str(&self.apply(&account_number, noise));
+            scanner_file.push('\n');
+            answer_key.push(AnswerKeyEntry {
+                account_number,
+                noise,
+            });
+        }
+        Fixture {
+            scanner_file,
+            answer_key,
+        }
+    }
+
+    /// Returns a random account number whose checksum is valid.
+    pub fn valid_account_number(&mut self) -> String {
+        loop {
+            let digits: Vec<usize> = (0..8).map(|_| self.rng.below(10)).collect();
+            let sum: usize = digits.iter().enumerate().map(|(i, d)| d * (9 - i)).sum();
+            // d1 の重みは1なので、残りの合計から d1 が決まる
+            let d1 = (11 - sum % 11) % 11;
+            if d1 < 10 {
+                return digits
+                    .iter()
+                    .chain(std::iter::once(&d1))
+                    .map(|d| (b'0' + *d as u8) as char)
+                    .collect();
+            }
+        }
+    }
+
+    /// Renders `account_number` as a scanner entry and damages it with `noise`.
+    pub fn apply(&mut self, account_number: &str, noise: Noise) -> String {
+        let entry = render_with_glyphs(account_number, &self.table)
+            .expect("account numbers only contain digits");
+        let mut lines: Vec<Vec<char>> = entry.lines().map(|l| l.chars().collect()).collect();
+        let digit_count = account_number.chars().count();
+
+        match noise {
+            Noise::Clean => {}
+            Noise::DropSegment => self.toggle_segment(&mut lines, digit_count, Some(true)),
+            Noise::AddSegment => self.toggle_segment(&mut lines, digit_count, Some(false)),
+            Noise::MultiSegment(n) => {
+                for _ in 0..n {
+                    self.toggle_segment(&mut lines, digit_count, None);
+                }
+            }
+            Noise::ShiftColumns => {
+                for line in lines.iter_mut().take(3) {
+                    line.insert(0, ' ');
+                }
+            }
+            Noise::StrayCharacter => {
+                let row = self.rng.below(3);
+                let column = self.rng.below(lines[row].len());
+                lines[row][column] = STRAY_CHARACTERS[self.rng.below(STRAY_CHARACTERS.len())];
+            }
+            Noise::TruncateLine => {
+                let row = self.rng.below(3);
+                let width = lines[row].len();
+                while lines[row].last() == Some(&' ') {
+                    lines[row].pop();
+                }
+                if lines[row].len() == width {
+                    lines[row].pop();
+                }
+            }
+        }
+
+        lines
+            .iter()
+            .map(|l| l.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Drops (`Some(true)`), adds (`Some(false)`) or toggles (`None`) one segment.
+    fn toggle_segment(&mut self, lines: &mut [Vec<char>], digit_count: usize, drop: Option<bool>) {
+        let mut candidates = Vec::new();
+        for digit in 0..digit_count {
+            for &(_, row, column, segment) in SEGMENT_CELLS.iter() {
+                let drawn = lines[row][digit * 3 + column] != ' ';
+                if drop.is_none_or(|drop| drop == drawn) {
+                    candidates.push((row, digit * 3 + column, segment, drawn));
+                }
+            }
+        }
+        if candidates.is_empty() {
+            return;
+        }
+        let (row, column, segment, drawn) = candidates[self.rng.below(candidates.len())];
+        lines[row][column] = if drawn { ' ' } else { segment };
+    }
+
+    fn pick_noise(&mut self) -> Noise {
+        let total: u32 = self.mix.iter().map(|(_, w)| w).sum();
+        if total == 0 {
+            return Noise::Clean;
+        }
+        let mut pick = self.rng.below(total as usize) as u32;
+        for &(noise, weight) in self.mix.iter() {
+            if pick < weight {
+                return noise;
+            }
+            pick -= weight;
+        }
+        Noise::Clean
+    }
+}
+
+#[cfg(test)]
+mod tests_noise_generator {
+    use super::*;
+    use crate::parser::{ENTRY_WIDTH, parse_entries, parse_entries_normalized};
+    use crate::recognizer::recognize_account_number;
+    use crate::resolver::{Resolution, resolve_account_number};
+    use crate::validator::is_valid_account_number;
+
+    #[test]
+    fn test_same_seed_same_fixture() {
+        let a = NoiseGenerator::new(42)
+            .with_noise(Noise::DropSegment, 1)
+            .with_noise(Noise::StrayCharacter, 1)
+            .generate(20);
+        let b = NoiseGenerator::new(42)
+            .with_noise(Noise::DropSegment, 1)
+            .with_noise(Noise::StrayCharacter, 1)
+            .generate(20);
+        assert_eq!(a, b);
+        assert_ne!(a, NoiseGenerator::new(43).generate(20));
+    }
+
+    #[test]
+    fn test_clean_fixture_matches_answer_key() {
+        let fixture = NoiseGenerator::new(1).generate(50);
+        let recognized: Vec<String> = parse_entries(fixture.scanner_file.as_bytes())
+            .map(|entry| recognize_account_number(&entry.unwrap().text()))
+            .collect();
+        let expected: Vec<String> = fixture
+            .answer_key
+            .iter()
+            .map(|entry| entry.account_number.clone())
+            .collect();
+        assert_eq!(expected, recognized);
+        assert!(expected.iter().all(|n| is_valid_account_number(n)));
+    }
+
+    #[test]
+    fn test_apply_noise() {
+        let mut generator = NoiseGenerator::new(7);
+        let clean = generator.apply("123456789", Noise::Clean);
+
+        let diff = |damaged: &str| {
+            clean
+                .chars()
+                .zip(damaged.chars())
+                .filter(|(a, b)| a != b)
+                .count()
+        };
+        assert_eq!(1, diff(&generator.apply("123456789", Noise::DropSegment)));
+        assert_eq!(1, diff(&generator.apply("123456789", Noise::AddSegment)));
+        assert!(diff(&generator.apply("123456789", Noise::MultiSegment(3))) <= 3);
+
+        // 右端の列も残る
+        let shifted = generator.apply("123456789", Noise::ShiftColumns);
+        for (shifted, clean) in shifted.lines().zip(clean.lines()).take(3) {
+            assert_eq!(format!(" {clean}"), shifted);
+        }
+        let entry = parse_entries_normalized(shifted.as_bytes(), ENTRY_WIDTH)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!("123456789", recognize_account_number(&entry.text()));
+
+        let truncated = generator.apply("123456789", Noise::TruncateLine);
+        assert!(truncated.lines().any(|l| l.chars().count() < ENTRY_WIDTH));
+        // 桁数が違っても描かれた行の幅で切り詰める
+        for _ in 0..10 {
+            let clean = generator.apply("11111", Noise::Clean);
+            let truncated = generator.apply("11111", Noise::TruncateLine);
+            let shorter = truncated
+                .lines()
+                .zip(clean.lines())
+                .filter(|(t, c)| t.len() < c.len())
+                .count();
+            assert_eq!(1, shorter);
+        }
+    }
+
+    #[test]
+    fn test_write_answer_key() {
+        let fixture = Fixture {
+            scanner_file: String::new(),
+            answer_key: vec![AnswerKeyEntry {
+                account_number: "457508000".to_string(),
+                noise: Noise::MultiSegment(2),
+            }],
+        };
+        let mut output = Vec::new();
+        fixture.write_answer_key(&mut output).unwrap();
+        assert_eq!(
+            "457508000 multi-segment-2\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    // 1本の線の欠落・追加は必ず候補に元の番号が含まれる
+    #[test]
+    fn test_single_segment_noise_is_recoverable() {
+        let fixture = NoiseGenerator::new(2024)
+            .with_noise(Noise::DropSegment, 1)
+            .with_noise(Noise::AddSegment, 1)
+            .generate(200);
+
+        let mut corrected = 0;
+        for (entry, answer) in
+            parse_entries(fixture.scanner_file.as_bytes()).zip(&fixture.answer_key)
+        {
+            let expected = &answer.account_number;
+            match resolve_account_number(&entry.unwrap().text()) {
+                Resolution::Valid(n) | Resolution::Corrected(n) => {
+                    assert_eq!(expected, &n);
+                    corrected += 1;
+                }
+                Resolution::Ambiguous { alternatives, .. } => {
+                    assert!(alternatives.contains(expected))
+                }
+                Resolution::Illegible(n) => panic!("{n} should be recoverable to {expected}"),
+            }
+        }
+        assert!(corrected > 0);
+    }
+}
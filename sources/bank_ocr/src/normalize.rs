@@ -0,0 +1,254 @@
+use std::fmt;
+
+use crate::glyph_table::GlyphTable;
+use crate::layout::Layout;
+
+/// Tabs are expanded to the next multiple of this column.
+pub const TAB_WIDTH: usize = 8;
+/// The largest horizontal drift that is detected, in columns.
+pub const MAX_OFFSET: usize = 2;
+
+/// A change made to the lines of an entry before recognition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Normalization {
+    /// `\r\n` line endings became `\n`.
+    StrippedCarriageReturns,
+    ExpandedTabs,
+    /// Lines with trimmed trailing spaces were padded to the entry width.
+    PaddedLines,
+    /// Trailing spaces beyond the entry width were removed.
+    TrimmedLines,
+    /// All digit lines were moved by this many columns; negative is to the left.
+    ShiftedColumns(isize),
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Normalization::StrippedCarriageReturns => write!(f, "strip-cr"),
+            Normalization::ExpandedTabs => write!(f, "expand-tabs"),
+            Normalization::PaddedLines => write!(f, "pad-lines"),
+            Normalization::TrimmedLines => write!(f, "trim-lines"),
+            Normalization::ShiftedColumns(offset) => write!(f, "shift-columns({offset:+})"),
+        }
+    }
+}
+
+/// Normalizes the digit lines of one entry in place and returns what was applied,
+/// in the order applied. Lines that are still longer than `width` afterwards
+/// hold something other than spaces there and are left for the parser to reject.
+pub fn normalize_lines(lines: &mut [String], width: usize) -> Vec<Normalization> {
+    normalize_lines_with(lines, width, &Layout::kata(), &GlyphTable::digits())
+}
+
+/// Like `normalize_lines`, for entries drawn in `layout` with the glyphs of `table`.
+pub fn normalize_lines_with(
+    lines: &mut [String],
+    width: usize,
+    layout: &Layout,
+    table: &GlyphTable,
+) -> Vec<Normalization> {
+    let mut applied = Vec::new();
+
+    if lines.iter().any(|l| l.ends_with('\r')) {
+        for line in lines.iter_mut() {
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        applied.push(Normalization::StrippedCarriageReturns);
+    }
+
+    if lines.iter().any(|l| l.contains('\t')) {
+        for line in lines.iter_mut() {
+            *line = expand_tabs(line);
+        }
+        applied.push(Normalization::ExpandedTabs);
+    }
+
+    let offset = detect_offset(lines, width, layout, table);
+    if offset != 0 {
+        for line in lines.iter_mut() {
+            *line = shift(line, offset);
+        }
+        applied.push(Normalization::ShiftedColumns(offset));
+    }
+
+    if lines.iter().any(|l| l.chars().count() < width) {
+        for line in lines.iter_mut() {
+            let length = line.chars().count();
+            if length < width {
+                line.extend(std::iter::repeat_n(' ', width - length));
+            }
+        }
+        applied.push(Normalization::PaddedLines);
+    }
+
+    if lines.iter().any(|l| l.chars().count() > width) {
+        let mut trimmed = false;
+        for line in lines.iter_mut() {
+            let kept: String = line.chars().take(width).collect();
+            if line.chars().count() > width && line[kept.len()..].trim().is_empty() {
+                *line = kept;
+                trimmed = true;
+            }
+        }
+        if trimmed {
+            applied.push(Normalization::TrimmedLines);
+        }
+    }
+
+    applied
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let next = (column / TAB_WIDTH + 1) * TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', next - column));
+            column = next;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+/// Moves `line` by `offset` columns: drops leading characters when negative,
+/// prepends spaces when positive.
+fn shift(line: &str, offset: isize) -> String {
+    if offset < 0 {
+        line.chars().skip(offset.unsigned_abs()).collect()
+    } else {
+        " ".repeat(offset as usize) + line
+    }
+}
+
+/// Returns the offset, within `MAX_OFFSET`, under which the most glyphs read as
+/// characters of `table`. Offsets that would drop a mark are not considered, and the nearest
+/// offset wins a tie, so the lines are left alone unless moving them helps.
+fn detect_offset(lines: &[String], width: usize, layout: &Layout, table: &GlyphTable) -> isize {
+    let count = width / layout.glyph_width;
+    let legible = |offset: isize| -> Option<usize> {
+        let shifted: Vec<String> = lines.iter().map(|l| shift(l, offset)).collect();
+        // 左にずらして消える列や、右にずらしてはみ出す列に線があってはいけない
+        let drops_mark = shifted.iter().zip(lines).any(|(s, l)| {
+            (offset < 0 && !l.chars().take(offset.unsigned_abs()).all(|c| c == ' '))
+                || s.chars().skip(width).any(|c| c != ' ')
+        });
+        if drops_mark {
+            return None;
+        }
+        let text = shifted.join("\n");
+        Some(
+            (0..count)
+                .filter(|&i| table.recognize(&layout.cut(&text, i)) != '?')
+                .count(),
+        )
+    };
+
+    let mut best: Option<(usize, isize)> = None;
+    let offsets = (1..=MAX_OFFSET as isize).flat_map(|d| [-d, d]);
+    for offset in std::iter::once(0).chain(offsets) {
+        if let Some(digits) = legible(offset)
+            && best.is_none_or(|(most, _)| digits > most)
+        {
+            best = Some((digits, offset));
+        }
+    }
+    best.map_or(0, |(_, offset)| offset)
+}
+
+#[cfg(test)]
+mod tests_normalize_lines {
+    use super::*;
+    use crate::recognizer::recognize_account_number;
+    use crate::renderer::render_account_number;
+    use crate::resolver::{Resolution, resolve_account_number};
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().take(3).map(String::from).collect()
+    }
+
+    #[test]
+    fn test_clean_lines_are_unchanged() {
+        let clean = lines(&render_account_number("123456789").unwrap());
+        let mut normalized = clean.clone();
+        assert!(normalize_lines(&mut normalized, 27).is_empty());
+        assert_eq!(clean, normalized);
+    }
+
+    #[test]
+    fn test_pad_trimmed_lines() {
+        let clean = lines(&render_account_number("111111111").unwrap());
+        let mut trimmed: Vec<String> = clean.iter().map(|l| l.trim_end().to_string()).collect();
+        assert_eq!(
+            vec![Normalization::PaddedLines],
+            normalize_lines(&mut trimmed, 27)
+        );
+        assert_eq!(clean, trimmed);
+    }
+
+    #[test]
+    fn test_crlf() {
+        let clean = lines(&render_account_number("123456789").unwrap());
+        let mut crlf: Vec<String> = clean.iter().map(|l| format!("{l}\r")).collect();
+        assert_eq!(
+            vec![Normalization::StrippedCarriageReturns],
+            normalize_lines(&mut crlf, 27)
+        );
+        assert_eq!(clean, crlf);
+    }
+
+    #[test]
+    fn test_tabs() {
+        assert_eq!("a       b", expand_tabs("a\tb"));
+        assert_eq!("        |", expand_tabs("    \t|"));
+
+        let clean = lines(&render_account_number("111111111").unwrap());
+        let mut tabs = clean.clone();
+        tabs[0] = "\t\t\t".to_string();
+        assert_eq!(
+            vec![Normalization::ExpandedTabs, Normalization::PaddedLines],
+            normalize_lines(&mut tabs, 27)
+        );
+        assert_eq!(clean, tabs);
+    }
+
+    #[test]
+    fn test_shifted_columns() {
+        let clean = render_account_number("490867715").unwrap();
+        let mut shifted: Vec<String> = lines(&clean).iter().map(|l| format!(" {l}")).collect();
+        assert_eq!(
+            vec![Normalization::ShiftedColumns(-1)],
+            normalize_lines(&mut shifted, 27)
+        );
+        assert_eq!(lines(&clean), shifted);
+
+        let mut left: Vec<String> = lines(&clean).iter().map(|l| l[1..].to_string()).collect();
+        assert_eq!(
+            vec![Normalization::ShiftedColumns(1)],
+            normalize_lines(&mut left, 27)
+        );
+        // 最初の列は失われたが、残りの桁は読める
+        assert_eq!("?90867715", recognize_account_number(&left.join("\n")));
+        assert_eq!(
+            Resolution::Corrected("490867715".to_string()),
+            resolve_account_number(&left.join("\n"))
+        );
+    }
+
+    #[test]
+    fn test_trailing_spaces_beyond_width() {
+        let clean = lines(&render_account_number("123456789").unwrap());
+        let mut long: Vec<String> = clean.iter().map(|l| format!("{l}   ")).collect();
+        assert_eq!(
+            vec![Normalization::TrimmedLines],
+            normalize_lines(&mut long, 27)
+        );
+        assert_eq!(clean, long);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::printer::print_ambiguous_account_number;
+use crate::registry::RegistryMatch;
+use crate::report::{EntryReport, ReportStatus};
+use crate::resolver::ChecksumEvidence;
+
+/// How an `EntryReport` is written: the kata's text, JSON Lines or CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// `457508000`, `664371495 ERR`, `86110??36 ILL` or `490067715 AMB [...]`.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    JsonLines,
+    /// A header line, then one row per entry.
+    Csv,
+}
+
+impl OutputFormat {
+    /// Returns the format called `name`: `text`, `jsonl` or `csv`.
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "jsonl" => Some(OutputFormat::JsonLines),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// The line written before the first entry, if any.
+    pub fn header(self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Csv => Some(
+                "source,index,line_number,raw,account_number,status,alternatives,confidence,normalizations,registry,checksum",
+            ),
+            _ => None,
+        }
+    }
+
+    /// Formats one entry as a single line, without the line break.
+    pub fn format(self, report: &EntryReport) -> String {
+        match self {
+            OutputFormat::Text => format_text(report),
+            OutputFormat::JsonLines => format_json(report),
+            OutputFormat::Csv => format_csv(report),
+        }
+    }
+}
+
+/// Numbers whose illegible digit was solved by the checksum alone, that were
+/// resolved or narrowed by the registry or that are not registered are followed
+/// by `(solved-by-checksum)`, `(resolved-by-registry)`, `(narrowed-by-registry)`
+/// or `(unregistered)`.
+fn format_text(report: &EntryReport) -> String {
+    let n = &report.account_number;
+    let mut text = match report.status {
+        ReportStatus::Ok | ReportStatus::Corrected | ReportStatus::Reviewed => n.clone(),
+        ReportStatus::Err => format!("{n} ERR"),
+        ReportStatus::Ill => format!("{n} ILL"),
+        ReportStatus::Amb => print_ambiguous_account_number(n, &report.alternatives),
+    };
+    if let Some(e @ ChecksumEvidence::Determined) = report.checksum {
+        text = format!("{text} ({})", e.label());
+    }
+    if let Some(
+        m @ (RegistryMatch::ResolvedAmbiguity
+        | RegistryMatch::NarrowedAmbiguity
+        | RegistryMatch::Unregistered),
+    ) = report.registry
+    {
+        text = format!("{text} ({})", m.label());
+    }
+    text
+}
+
+fn format_json(report: &EntryReport) -> String {
+    let alternatives: Vec<String> = report.alternatives.iter().map(|a| json_string(a)).collect();
+    let confidences: Vec<String> = report
+        .confidences
+        .iter()
+        .map(|c| format!("{c:.4}"))
+        .collect();
+    let normalizations: Vec<String> = report
+        .normalizations
+        .iter()
+        .map(|n| json_string(&n.to_string()))
+        .collect();
+    format!(
+        r#"{{"source":{},"index":{},"line_number":{},"raw":{},"account_number":{},"status":{},"alternatives":[{}],"confidence":[{}],"normalizations":[{}],"registry":{},"checksum":{}}}"#,
+        report
+            .source
+            .as_deref()
+            .map_or("null".to_string(), json_string),
+        report.index,
+        report.line_number,
+        json_string(&report.raw.number()),
+        json_string(&report.account_number),
+        json_string(report.status.label()),
+        alternatives.join(","),
+        confidences.join(","),
+        normalizations.join(","),
+        report
+            .registry
+            .map_or("null".to_string(), |m| json_string(m.label())),
+        report
+            .checksum
+            .map_or("null".to_string(), |e| json_string(e.label()))
+    )
+}
+
+/// Alternatives, confidences and normalizations are joined by `;` within their fields.
+fn format_csv(report: &EntryReport) -> String {
+    let confidences: Vec<String> = report
+        .confidences
+        .iter()
+        .map(|c| format!("{c:.4}"))
+        .collect();
+    let normalizations: Vec<String> = report
+        .normalizations
+        .iter()
+        .map(|n| n.to_string())
+        .collect();
+    format!(
+        "{},{},{},{},{},{},{},{},{},{},{}",
+        csv_field(report.source.as_deref().unwrap_or("")),
+        report.index,
+        report.line_number,
+        csv_field(&report.raw.number()),
+        csv_field(&report.account_number),
+        report.status.label(),
+        csv_field(&report.alternatives.join(";")),
+        confidences.join(";"),
+        normalizations.join(";"),
+        report.registry.map_or("", RegistryMatch::label),
+        report.checksum.map_or("", ChecksumEvidence::label)
+    )
+}
+
+/// Quotes `s` as a JSON string.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes `s` for a CSV field if it needs to be.
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests_output_format {
+    use super::*;
+    use crate::normalize::Normalization;
+
+    fn report() -> EntryReport {
+        EntryReport {
+            source: Some("scans/a,b.txt".to_string()),
+            index: 2,
+            line_number: 9,
+            raw: "490067715".parse().unwrap(),
+            account_number: "490067715".to_string(),
+            status: ReportStatus::Amb,
+            alternatives: vec!["490067115".to_string(), "490867715".to_string()],
+            confidences: vec![0.5, 0.25],
+            normalizations: vec![
+                Normalization::StrippedCarriageReturns,
+                Normalization::ShiftedColumns(-1),
+            ],
+            lines: Vec::new(),
+            registry: None,
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            "490067715 AMB ['490067115', '490867715']",
+            OutputFormat::Text.format(&report())
+        );
+        let mut err = report();
+        err.status = ReportStatus::Err;
+        assert_eq!("490067715 ERR", OutputFormat::Text.format(&err));
+        err.registry = Some(RegistryMatch::Unregistered);
+        assert_eq!(
+            "490067715 ERR (unregistered)",
+            OutputFormat::Text.format(&err)
+        );
+        let mut solved = report();
+        solved.account_number = "4900677?5".to_string();
+        solved.status = ReportStatus::Ill;
+        solved.checksum = Some(ChecksumEvidence::Determined);
+        assert_eq!(
+            "4900677?5 ILL (solved-by-checksum)",
+            OutputFormat::Text.format(&solved)
+        );
+        assert_eq!(None, OutputFormat::Text.header());
+    }
+
+    #[test]
+    fn test_json_lines() {
+        assert_eq!(
+            r#"{"source":"scans/a,b.txt","index":2,"line_number":9,"raw":"490067715","account_number":"490067715","status":"AMB","alternatives":["490067115","490867715"],"confidence":[0.5000,0.2500],"normalizations":["strip-cr","shift-columns(-1)"],"registry":null,"checksum":null}"#,
+            OutputFormat::JsonLines.format(&report())
+        );
+        let mut stdin = report();
+        stdin.source = None;
+        assert!(
+            OutputFormat::JsonLines
+                .format(&stdin)
+                .starts_with(r#"{"source":null,"#)
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            r#""scans/a,b.txt",2,9,490067715,490067715,AMB,490067115;490867715,0.5000;0.2500,strip-cr;shift-columns(-1),,"#,
+            OutputFormat::Csv.format(&report())
+        );
+        assert_eq!(11, OutputFormat::Csv.header().unwrap().split(',').count());
+    }
+
+    #[test]
+    fn test_by_name() {
+        assert_eq!(
+            Some(OutputFormat::JsonLines),
+            OutputFormat::by_name("jsonl")
+        );
+        assert_eq!(None, OutputFormat::by_name("xml"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(r#""a\"b\\c\n""#, json_string("a\"b\\c\n"));
+        assert_eq!("plain", csv_field("plain"));
+        assert_eq!(r#""a,""b""""#, csv_field("a,\"b\""));
+    }
+}
@@ -0,0 +1,500 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead};
+
+use crate::glyph_table::GlyphTable;
+use crate::layout::Layout;
+use crate::normalize::{Normalization, normalize_lines_with};
+
+pub const ENTRY_WIDTH: usize = 27;
+pub const DIGIT_LINES: usize = 3;
+
+/// One entry read from a scanner file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// 0-based position of the entry in the source.
+    pub index: usize,
+    /// 1-based line number of the first line of the entry.
+    pub line_number: usize,
+    /// The lines holding the digits, `Layout::glyph_height` of them.
+    pub lines: Vec<String>,
+    /// What was changed to read the lines, see `parse_entries_normalized`.
+    pub normalizations: Vec<Normalization>,
+}
+
+impl Entry {
+    /// Returns the digit lines joined by `\n`, as accepted by `recognizer::recognize_account_number`.
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    InvalidLineLength {
+        line_number: usize,
+        expected: usize,
+        length: usize,
+    },
+    MissingBlankLine {
+        line_number: usize,
+    },
+    TruncatedEntry {
+        line_number: usize,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "could not read scanner file: {e}"),
+            ParseError::InvalidLineLength {
+                line_number,
+                expected,
+                length,
+            } => write!(
+                f,
+                "line {line_number}: expected {expected} characters but found {length}"
+            ),
+            ParseError::MissingBlankLine { line_number } => {
+                write!(f, "line {line_number}: expected a blank separator line")
+            }
+            ParseError::TruncatedEntry { line_number } => {
+                write!(f, "line {line_number}: entry is truncated")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+/// Streams entries from a scanner file one at a time.
+///
+/// Each entry is three lines of `width` characters followed by a blank line,
+/// or as many lines as `Layout::glyph_height`, see `Entries::with_layout`.
+/// The blank line may be omitted after the last entry of the file.
+///
+/// A broken entry is yielded as an error, and reading resumes after the next
+/// blank line, so the entries that follow it are still read. Without blank
+/// lines between entries, reading resumes right after the broken entry.
+pub struct Entries<R> {
+    reader: R,
+    width: usize,
+    layout: Layout,
+    table: GlyphTable,
+    /// Lines read past a broken entry that belong to the next one, by line number.
+    pending: VecDeque<(usize, String)>,
+    /// An error met while skipping a broken entry, yielded next.
+    deferred: Option<ParseError>,
+    line_number: usize,
+    index: usize,
+    normalize: bool,
+    finished: bool,
+}
+
+pub fn parse_entries<R: BufRead>(reader: R) -> Entries<R> {
+    parse_entries_with_width(reader, ENTRY_WIDTH)
+}
+
+/// Parses entries whose lines are `width` characters, e.g. `AccountFormat::width()`.
+pub fn parse_entries_with_width<R: BufRead>(reader: R, width: usize) -> Entries<R> {
+    Entries {
+        reader,
+        width,
+        layout: Layout::kata(),
+        table: GlyphTable::digits(),
+        pending: VecDeque::new(),
+        deferred: None,
+        line_number: 0,
+        index: 0,
+        normalize: false,
+        finished: false,
+    }
+}
+
+/// Like `parse_entries_with_width`, but normalizes the lines of every entry
+/// first (see `normalize::normalize_lines`), so trimmed, drifted, tabbed or
+/// CRLF lines are accepted.
+pub fn parse_entries_normalized<R: BufRead>(reader: R, width: usize) -> Entries<R> {
+    Entries {
+        normalize: true,
+        ..parse_entries_with_width(reader, width)
+    }
+}
+
+impl<R: BufRead> Entries<R> {
+    /// Reads entries drawn in `layout`: its number of lines per entry, with or
+    /// without a blank line after each, and its glyphs when normalizing.
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Realigns drifted lines by the glyphs of `table` when normalizing.
+    pub fn with_table(mut self, table: GlyphTable) -> Self {
+        self.table = table;
+        self
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, ParseError> {
+        if let Some((line_number, line)) = self.pending.pop_front() {
+            self.line_number = line_number;
+            return Ok(Some(line));
+        }
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        self.line_number += 1;
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    fn read_entry(&mut self) -> Result<Option<Entry>, ParseError> {
+        let line_number = self
+            .pending
+            .front()
+            .map_or(self.line_number + 1, |(n, _)| *n);
+        let mut lines = Vec::with_capacity(self.layout.glyph_height);
+        while lines.len() < self.layout.glyph_height {
+            match self.read_line()? {
+                Some(line) => lines.push(line),
+                None if lines.is_empty() => return Ok(None),
+                None => {
+                    return Err(ParseError::TruncatedEntry {
+                        line_number: self.line_number + 1,
+                    });
+                }
+            }
+        }
+
+        let read = self.normalize.then(|| lines.clone());
+        let normalizations = if self.normalize {
+            normalize_lines_with(&mut lines, self.width, &self.layout, &self.table)
+        } else {
+            Vec::new()
+        };
+        for (i, line) in lines.iter().enumerate() {
+            let length = line.chars().count();
+            if length != self.width {
+                let error = ParseError::InvalidLineLength {
+                    line_number: line_number + i,
+                    expected: self.width,
+                    length,
+                };
+                self.resync(line_number, read.unwrap_or(lines));
+                return Err(error);
+            }
+        }
+
+        if self.layout.separator
+            && let Some(separator) = self.read_line()?
+            && !separator.trim().is_empty()
+        {
+            let error = ParseError::MissingBlankLine {
+                line_number: self.line_number,
+            };
+            self.resync(self.line_number, vec![separator]);
+            return Err(error);
+        }
+
+        let entry = Entry {
+            index: self.index,
+            line_number,
+            lines,
+            normalizations,
+        };
+        self.index += 1;
+        Ok(Some(entry))
+    }
+
+    /// Moves past the next blank line after a broken entry, which read `lines`
+    /// from `line_number` on. Lines read after that blank line are kept for
+    /// the next entry. The broken entry keeps its index.
+    fn resync(&mut self, line_number: usize, lines: Vec<String>) {
+        self.index += 1;
+        if !self.layout.separator {
+            return;
+        }
+        match lines.iter().position(|l| l.trim().is_empty()) {
+            Some(blank) => {
+                let rest = lines.into_iter().enumerate().skip(blank + 1);
+                for (i, line) in rest.rev() {
+                    self.pending.push_front((line_number + i, line));
+                }
+            }
+            None => self.skip_to_separator(),
+        }
+    }
+
+    fn skip_to_separator(&mut self) {
+        loop {
+            match self.read_line() {
+                Ok(Some(line)) if !line.trim().is_empty() => {}
+                Ok(_) => break,
+                Err(e) => {
+                    self.deferred = Some(e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Entries<R> {
+    type Item = Result<Entry, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let result = match self.deferred.take() {
+            Some(e) => Some(Err(e)),
+            None => self.read_entry().transpose(),
+        };
+        if matches!(result, Some(Err(ParseError::Io(_)))) {
+            self.finished = true;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests_parse_entries {
+    use super::*;
+    use crate::recognizer::recognize_account_number;
+
+    const ONE_TO_NINE: &str = "    _  _     _  _  _  _  _ \n  | _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n                           \n";
+    const ZEROS: &str = " _  _  _  _  _  _  _  _  _ \n| || || || || || || || || |\n|_||_||_||_||_||_||_||_||_|\n                           \n";
+
+    #[test]
+    fn test_parse_multiple_entries() {
+        let input = format!("{ONE_TO_NINE}{ZEROS}");
+        let entries: Vec<Entry> = parse_entries(input.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].index, 0);
+        assert_eq!(entries[0].line_number, 1);
+        assert_eq!(entries[1].index, 1);
+        assert_eq!(entries[1].line_number, 5);
+        assert_eq!("123456789", recognize_account_number(&entries[0].text()));
+        assert_eq!("000000000", recognize_account_number(&entries[1].text()));
+    }
+
+    #[test]
+    fn test_parse_last_entry_without_blank_line() {
+        let input = ONE_TO_NINE.trim_end_matches([' ', '\n']).to_string() + "\n";
+        let entries: Vec<Entry> = parse_entries(input.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_empty_input() {
+        assert!(parse_entries("".as_bytes()).next().is_none());
+    }
+
+    #[test]
+    fn test_parse_invalid_line_length() {
+        let input = format!("{ONE_TO_NINE}{}", ZEROS.replacen("| |\n", "|\n", 1));
+        let mut entries = parse_entries(input.as_bytes());
+        assert!(entries.next().unwrap().is_ok());
+        assert!(matches!(
+            entries.next(),
+            Some(Err(ParseError::InvalidLineLength {
+                line_number: 6,
+                expected: 27,
+                length: 25
+            }))
+        ));
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn test_resume_after_broken_entry() {
+        // 2件目は行が短く、3件目は空行の代わりにゴミがあり (4件目ごと読み飛ばす)、
+        // 5件目は1行しかない
+        let short = ZEROS.replacen("| |\n", "|\n", 1);
+        let no_blank = ONE_TO_NINE.replacen(&" ".repeat(27), "garbage", 1);
+        let one_line = format!("{}\n\n", ZEROS.lines().next().unwrap());
+        let input = format!("{ONE_TO_NINE}{short}{no_blank}{ZEROS}{one_line}{ONE_TO_NINE}{ZEROS}");
+        let results: Vec<Result<Entry, ParseError>> = parse_entries(input.as_bytes()).collect();
+
+        assert_eq!(6, results.len());
+        assert!(matches!(
+            results[1],
+            Err(ParseError::InvalidLineLength { line_number: 6, .. })
+        ));
+        assert!(matches!(
+            results[2],
+            Err(ParseError::MissingBlankLine { line_number: 12 })
+        ));
+        assert!(matches!(
+            results[3],
+            Err(ParseError::InvalidLineLength {
+                line_number: 18,
+                length: 0,
+                ..
+            })
+        ));
+        let entries: Vec<&Entry> = results.iter().flatten().collect();
+        assert_eq!(
+            vec![(0, 1), (4, 19), (5, 23)],
+            entries
+                .iter()
+                .map(|e| (e.index, e.line_number))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("123456789", recognize_account_number(&entries[1].text()));
+        assert_eq!("000000000", recognize_account_number(&entries[2].text()));
+    }
+
+    /// Fails once after its data, then reads as empty.
+    struct FailingReader {
+        data: &'static [u8],
+        failed: bool,
+    }
+
+    impl io::Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.data.is_empty() {
+                let n = buf.len().min(self.data.len());
+                buf[..n].copy_from_slice(&self.data[..n]);
+                self.data = &self.data[n..];
+                return Ok(n);
+            }
+            if self.failed {
+                return Ok(0);
+            }
+            self.failed = true;
+            Err(io::Error::other("disk unplugged"))
+        }
+    }
+
+    #[test]
+    fn test_io_error_while_skipping_broken_entry() {
+        let reader = io::BufReader::new(FailingReader {
+            data: b"    _  _     _  _  _  _  _ \n  | _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n\nshort\nxx\nyy\n",
+            failed: false,
+        });
+        let mut entries = parse_entries(reader);
+        assert!(entries.next().unwrap().is_ok());
+        assert!(matches!(
+            entries.next(),
+            Some(Err(ParseError::InvalidLineLength { line_number: 5, .. }))
+        ));
+        // 読み飛ばし中のエラーも失われない
+        assert!(matches!(entries.next(), Some(Err(ParseError::Io(_)))));
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_other_width() {
+        let input = "    _ \n  || |\n  ||_|\n      \n";
+        let entries: Vec<Entry> = parse_entries_with_width(input.as_bytes(), 6)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(parse_entries(input.as_bytes()).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_parse_missing_blank_line() {
+        let input = format!("{}\n{ZEROS}", ONE_TO_NINE.trim_end_matches([' ', '\n']));
+        let mut entries = parse_entries(input.as_bytes());
+        assert!(matches!(
+            entries.next(),
+            Some(Err(ParseError::MissingBlankLine { line_number: 4 }))
+        ));
+    }
+
+    #[test]
+    fn test_parse_truncated_entry() {
+        let input = format!("{ONE_TO_NINE}{}", &ZEROS[..56]);
+        let mut entries = parse_entries(input.as_bytes());
+        assert!(entries.next().unwrap().is_ok());
+        assert!(matches!(
+            entries.next(),
+            Some(Err(ParseError::TruncatedEntry { line_number: 7 }))
+        ));
+    }
+
+    #[test]
+    fn test_parse_normalized() {
+        // 1件目は行末の空白が削られ CRLF、2件目は右に1列ずれている
+        let trimmed: String = ONE_TO_NINE
+            .lines()
+            .map(|l| format!("{}\r\n", l.trim_end()))
+            .collect();
+        let shifted: String = ZEROS.lines().map(|l| format!(" {l}\n")).collect();
+        let input = format!("{trimmed}{shifted}");
+        assert!(parse_entries(input.as_bytes()).next().unwrap().is_err());
+
+        let entries: Vec<Entry> = parse_entries_normalized(input.as_bytes(), ENTRY_WIDTH)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            vec![
+                Normalization::StrippedCarriageReturns,
+                Normalization::PaddedLines
+            ],
+            entries[0].normalizations
+        );
+        assert_eq!(
+            vec![Normalization::ShiftedColumns(-1)],
+            entries[1].normalizations
+        );
+        assert_eq!("123456789", recognize_account_number(&entries[0].text()));
+        assert_eq!("000000000", recognize_account_number(&entries[1].text()));
+    }
+}
+
+#[cfg(test)]
+mod tests_parser_properties {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn arbitrary_bytes_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..300)) {
+            for entry in parse_entries(bytes.as_slice()) {
+                let _ = entry;
+            }
+            for entry in parse_entries_normalized(bytes.as_slice(), ENTRY_WIDTH) {
+                let _ = entry;
+            }
+        }
+
+        #[test]
+        fn scanner_like_lines_do_not_panic(
+            lines in prop::collection::vec("[ _|\t\r]{0,32}", 0..16),
+        ) {
+            let file = lines.join("\n");
+            for entry in parse_entries_normalized(file.as_bytes(), ENTRY_WIDTH).flatten() {
+                prop_assert_eq!(DIGIT_LINES, entry.lines.len());
+                prop_assert!(entry.lines.iter().all(|l| l.chars().count() == ENTRY_WIDTH));
+            }
+        }
+    }
+}
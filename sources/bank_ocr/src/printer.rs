@@ -1,21 +1,49 @@
+use crate::account_number::AccountNumber;
+use crate::checksum::AccountFormat;
+use crate::resolver::Resolution;
+
 pub fn print_account_number(account_number: &str) -> String {
-    let mut account_number = account_number.to_string();
+    match account_number.parse::<AccountNumber>() {
+        Ok(parsed) if parsed.number() == account_number => parsed.to_string(),
+        _ if account_number.contains('?') => format!("{account_number} ILL"),
+        _ if !crate::validator::is_valid_account_number(account_number) => {
+            format!("{account_number} ERR")
+        }
+        _ => account_number.to_string(),
+    }
+}
+
+pub fn print_account_number_with(account_number: &str, format: &AccountFormat) -> String {
     if account_number.contains('?') {
-        account_number.push_str(" ILL");
-        account_number
-    } else if crate::validator::is_valid_account_number(&account_number) == false {
-        account_number.push_str(" ERR");
-        account_number
+        format!("{account_number} ILL")
+    } else if !format.is_valid(account_number) {
+        format!("{account_number} ERR")
     } else {
-        account_number
+        account_number.to_string()
+    }
+}
+
+pub fn print_ambiguous_account_number(account_number: &str, alternatives: &[String]) -> String {
+    let alternatives: Vec<String> = alternatives.iter().map(|a| format!("'{a}'")).collect();
+    format!("{account_number} AMB [{}]", alternatives.join(", "))
+}
+
+pub fn print_resolution(resolution: &Resolution) -> String {
+    match resolution {
+        Resolution::Valid(account_number) | Resolution::Corrected(account_number) => {
+            account_number.clone()
+        }
+        Resolution::Ambiguous {
+            account_number,
+            alternatives,
+        } => print_ambiguous_account_number(account_number, alternatives),
+        Resolution::Illegible(account_number) => format!("{account_number} ILL"),
     }
 }
 
 #[cfg(test)]
 mod tests_print_result {
     use super::*;
-    use crate::recognizer;
-    use crate::validator;
 
     #[test]
     fn test_print_account_number_true() {
@@ -31,4 +59,54 @@ mod tests_print_result {
     fn test_print_account_number_invalid_number() {
         assert_eq!("86110??36 ILL", print_account_number("86110??36"));
     }
+
+    #[test]
+    fn test_print_account_number_not_nine_digits() {
+        assert_eq!("12?4 ILL", print_account_number("12?4"));
+        assert_eq!("12345 ERR", print_account_number("12345"));
+    }
+
+    #[test]
+    fn test_print_account_number_with() {
+        let format = AccountFormat::new(4, Box::new(crate::checksum::Verhoeff));
+        assert_eq!("2363", print_account_number_with("2363", &format));
+        assert_eq!("2364 ERR", print_account_number_with("2364", &format));
+        assert_eq!("23?3 ILL", print_account_number_with("23?3", &format));
+    }
+
+    #[test]
+    fn test_print_ambiguous_account_number() {
+        let alternatives = vec!["555655555".to_string(), "559555555".to_string()];
+        assert_eq!(
+            "555555555 AMB ['555655555', '559555555']",
+            print_ambiguous_account_number("555555555", &alternatives)
+        );
+    }
+
+    #[test]
+    fn test_print_resolution() {
+        assert_eq!(
+            "457508000",
+            print_resolution(&Resolution::Valid("457508000".to_string()))
+        );
+        assert_eq!(
+            "490867715",
+            print_resolution(&Resolution::Corrected("490867715".to_string()))
+        );
+        assert_eq!(
+            "490067715 AMB ['490067115', '490067719', '490867715']",
+            print_resolution(&Resolution::Ambiguous {
+                account_number: "490067715".to_string(),
+                alternatives: vec![
+                    "490067115".to_string(),
+                    "490067719".to_string(),
+                    "490867715".to_string()
+                ],
+            })
+        );
+        assert_eq!(
+            "1234?678? ILL",
+            print_resolution(&Resolution::Illegible("1234?678?".to_string()))
+        );
+    }
 }
@@ -1,4 +1,8 @@
-use std::result;
+use crate::account_number::ACCOUNT_NUMBER_LENGTH;
+use crate::checksum::AccountFormat;
+use crate::glyph_table::GlyphTable;
+use crate::layout::Layout;
+use crate::segments::{DIGIT_SEGMENTS, read_glyph};
 
 pub const ZERO_PATTERN: [[char; 3]; 3] = [[' ', '_', ' '], ['|', ' ', '|'], ['|', '_', '|']];
 pub const ONE_PATTERN: [[char; 3]; 3] = [[' ', ' ', ' '], [' ', ' ', '|'], [' ', ' ', '|']];
@@ -12,19 +16,12 @@ pub const EIGHT_PATTERN: [[char; 3]; 3] = [[' ', '_', ' '], ['|', '_', '|'], ['|
 pub const NINE_PATTERN: [[char; 3]; 3] = [[' ', '_', ' '], ['|', '_', '|'], [' ', '_', '|']];
 
 pub fn recognize_digit(pattern: [[char; 3]; 3]) -> char {
-    match pattern {
-        ZERO_PATTERN => '0',
-        ONE_PATTERN => '1',
-        TWO_PATTERN => '2',
-        THREE_PATTERN => '3',
-        FOUR_PATTERN => '4',
-        FIVE_PATTERN => '5',
-        SIX_PATTERN => '6',
-        SEVEN_PATTERN => '7',
-        EIGHT_PATTERN => '8',
-        NINE_PATTERN => '9',
-        _ => '?',
-    }
+    recognize_digit_with(pattern, &GlyphTable::digits())
+}
+
+/// Recognizes a glyph with any table, e.g. `GlyphTable::hex()`.
+pub fn recognize_digit_with(pattern: [[char; 3]; 3], table: &GlyphTable) -> char {
+    table.recognize(&pattern)
 }
 
 pub fn get_digit_pattern(digit: char) -> [[char; 3]; 3] {
@@ -44,21 +41,49 @@ pub fn get_digit_pattern(digit: char) -> [[char; 3]; 3] {
 }
 
 pub fn recognize_account_number(numbers: &str) -> String {
+    recognize_digits(numbers, ACCOUNT_NUMBER_LENGTH)
+}
+
+pub fn recognize_digits(numbers: &str, count: usize) -> String {
+    recognize_digits_with(numbers, count, &GlyphTable::digits())
+}
+
+/// Recognizes the `format.length` glyphs of an entry drawn in `format.layout`
+/// with `format.table`.
+pub fn recognize_entry(numbers: &str, format: &AccountFormat) -> String {
+    (0..format.length)
+        .map(|i| format.table.recognize(&format.layout.cut(numbers, i)))
+        .collect()
+}
+
+pub fn recognize_digits_with(numbers: &str, count: usize, table: &GlyphTable) -> String {
     let mut result = String::new();
-    for i in 0..9 {
+    for i in 0..count {
         let pattern = cat_number(numbers, i);
-        result.push(recognize_digit(pattern));
+        result.push(recognize_digit_with(pattern, table));
     }
     result
 }
 
 // 新しい関数をここに実装するよ
 pub fn correct_account_number(original_number_pattern: &str, account_number: &str) -> Vec<String> {
+    correct_account_number_with(
+        original_number_pattern,
+        account_number,
+        &AccountFormat::kata(),
+    )
+}
+
+pub fn correct_account_number_with(
+    original_number_pattern: &str,
+    account_number: &str,
+    format: &AccountFormat,
+) -> Vec<String> {
     let mut correct_number_list = Vec::new();
     let original_account_chars: Vec<char> = account_number.chars().collect();
 
-    for i in 0..9 {
-        let target_pattern = cat_number(original_number_pattern, i);
+    for i in 0..original_account_chars.len() {
+        let target_pattern = format.layout.cut(original_number_pattern, i);
         let one_off_digits = generate_one_off_patterns(target_pattern);
 
         for &candidate_digit_char in one_off_digits.iter() {
@@ -66,7 +91,7 @@ pub fn correct_account_number(original_number_pattern: &str, account_number: &st
             temp_chars[i] = candidate_digit_char;
             let candidate_string: String = temp_chars.into_iter().collect();
 
-            if crate::validator::is_valid_account_number(&candidate_string) {
+            if format.is_valid(&candidate_string) {
                 correct_number_list.push(candidate_string);
             }
         }
@@ -74,51 +99,21 @@ pub fn correct_account_number(original_number_pattern: &str, account_number: &st
     correct_number_list
 }
 
+/// Cuts out the glyph of the `index`-th digit. Cells past the end of a short
+/// line, or of missing lines, read as spaces. See `Layout::cut` for other layouts.
 pub fn cat_number(line: &str, index: usize) -> [[char; 3]; 3] {
-    let mut result: [[char; 3]; 3] = [[' ', ' ', ' '], [' ', ' ', ' '], [' ', ' ', ' ']];
-    let lines_char: Vec<Vec<char>> = line
-        .lines()
-        .map(|l| l.chars().collect::<Vec<char>>())
-        .collect::<Vec<Vec<char>>>();
-
-    for i in 0..3 {
-        for j in 0..3 {
-            result[i][j] = lines_char[i][index * 3 + j];
-        }
-    }
-
-    result
+    Layout::kata().cut(line, index)
 }
 
+/// Returns the digits one segment edit away from the scanned glyph, including the
+/// digit itself if it matches. A mark outside the segments counts as one edit.
 pub fn generate_one_off_patterns(pattern: [[char; 3]; 3]) -> Vec<char> {
-    fn diff_count(a: &[[char; 3]; 3], b: &[[char; 3]; 3]) -> usize {
-        let mut count = 0;
-        for c in 0..3 {
-            for r in 0..3 {
-                if a[c][r] != b[c][r] {
-                    count += 1;
-                }
-            }
-        }
-        count
-    }
-    let numbers = [
-        ZERO_PATTERN,
-        ONE_PATTERN,
-        TWO_PATTERN,
-        THREE_PATTERN,
-        FOUR_PATTERN,
-        FIVE_PATTERN,
-        SIX_PATTERN,
-        SEVEN_PATTERN,
-        EIGHT_PATTERN,
-        NINE_PATTERN,
-    ];
+    let reading = read_glyph(&pattern);
     let mut result = Vec::new();
 
-    for (index, number) in numbers.iter().enumerate() {
-        if diff_count(&pattern, &number) <= 1 {
-            result.push((index as u8 + b'0' as u8) as char);
+    for (index, number) in DIGIT_SEGMENTS.iter().enumerate() {
+        if reading.edits_to(*number) <= 1 {
+            result.push((index as u8 + b'0') as char);
         }
     }
     result
@@ -132,23 +127,31 @@ mod tests_common {
     fn test_cat_number() {
         let numbers =
             "    _  _     _  _  _  _  _ \n  | _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|";
-        let one = cat_number(&numbers, 0);
+        let one = cat_number(numbers, 0);
         assert_eq!(one, ONE_PATTERN);
-        let two = cat_number(&numbers, 1);
+        let two = cat_number(numbers, 1);
         assert_eq!(two, TWO_PATTERN);
-        let three = cat_number(&numbers, 2);
+        let three = cat_number(numbers, 2);
         assert_eq!(three, THREE_PATTERN);
-        let four = cat_number(&numbers, 3);
+        let four = cat_number(numbers, 3);
         assert_eq!(four, FOUR_PATTERN);
-        let six = cat_number(&numbers, 5);
+        let six = cat_number(numbers, 5);
         assert_eq!(six, SIX_PATTERN);
-        let seven = cat_number(&numbers, 6);
+        let seven = cat_number(numbers, 6);
         assert_eq!(seven, SEVEN_PATTERN);
-        let eight = cat_number(&numbers, 7);
+        let eight = cat_number(numbers, 7);
         assert_eq!(eight, EIGHT_PATTERN);
-        let nine = cat_number(&numbers, 8);
+        let nine = cat_number(numbers, 8);
         assert_eq!(nine, NINE_PATTERN);
     }
+
+    #[test]
+    fn test_cat_number_short_lines() {
+        let trimmed = "\n  |  |\n  |  |";
+        assert_eq!(ONE_PATTERN, cat_number(trimmed, 1));
+        assert_eq!([[' '; 3]; 3], cat_number(trimmed, 8));
+        assert_eq!([[' '; 3]; 3], cat_number("", 0));
+    }
 }
 
 #[cfg(test)]
@@ -174,6 +177,41 @@ mod tests_recognize_number {
             assert_eq!(recognize_digit(*pattern), *expected);
         }
     }
+
+    #[test]
+    fn test_recognize_with_glyph_table() {
+        use crate::renderer::render_with_glyphs;
+
+        let mut table = GlyphTable::hex();
+        table.insert('-', GlyphTable::dash().segments('-').unwrap());
+        let pattern = render_with_glyphs("AbCdEF-09", &table).unwrap();
+        assert_eq!("AbCdEF-09", recognize_digits_with(&pattern, 9, &table));
+        assert_eq!(
+            "AbCdEF?09",
+            recognize_digits_with(&pattern, 9, &GlyphTable::hex())
+        );
+        assert_eq!(
+            "??????-09",
+            recognize_digits_with(&pattern, 9, &GlyphTable::dash())
+        );
+        assert_eq!("???????09", recognize_digits(&pattern, 9));
+    }
+
+    #[test]
+    fn test_recognize_entry_with_format_table() {
+        use crate::checksum::Mod11;
+        use crate::renderer::render_with_glyphs;
+        use crate::scoring::score_entry;
+
+        let pattern = render_with_glyphs("AbCdEF009", &GlyphTable::hex()).unwrap();
+        let format = AccountFormat::new(9, Box::new(Mod11)).with_table(GlyphTable::hex());
+        assert_eq!("AbCdEF009", recognize_entry(&pattern, &format));
+        assert_eq!("AbCdEF009", score_entry(&pattern, &format).exact());
+        assert_eq!(
+            "??????009",
+            recognize_entry(&pattern, &AccountFormat::kata())
+        );
+    }
 }
 
 #[cfg(test)]
@@ -186,7 +224,7 @@ mod tests_recognize_account_number {
 
         assert_eq!(
             "000000000",
-            recognize_account_number(&zero_account_number_pattern)
+            recognize_account_number(zero_account_number_pattern)
         );
     }
 
@@ -196,7 +234,7 @@ mod tests_recognize_account_number {
 
         assert_eq!(
             "111111111",
-            recognize_account_number(&zero_account_number_pattern)
+            recognize_account_number(zero_account_number_pattern)
         );
     }
 
@@ -206,7 +244,7 @@ mod tests_recognize_account_number {
 
         assert_eq!(
             "123456789",
-            recognize_account_number(&zero_account_number_pattern)
+            recognize_account_number(zero_account_number_pattern)
         );
     }
 }
@@ -220,28 +258,28 @@ mod tests_one_off_patterns {
         // Test Case 1: '0' -> '8' (add char)
         let zero_pattern = ZERO_PATTERN;
         let one_off_patterns_from_zero = generate_one_off_patterns(zero_pattern);
-        assert!(one_off_patterns_from_zero.contains(&('8' as char)));
+        assert!(one_off_patterns_from_zero.contains(&'8'));
 
         // Test Case 2: '8' -> '0' (remove char)
         let eight_pattern = EIGHT_PATTERN;
         let one_off_patterns_from_eight = generate_one_off_patterns(eight_pattern);
-        assert!(one_off_patterns_from_eight.contains(&('0' as char)));
+        assert!(one_off_patterns_from_eight.contains(&'0'));
 
         // Test Case 3: '1' -> '7' (add char)
         let one_pattern = ONE_PATTERN;
         let one_off_patterns_from_one = generate_one_off_patterns(one_pattern);
-        assert!(one_off_patterns_from_one.contains(&('7' as char)));
+        assert!(one_off_patterns_from_one.contains(&'7'));
 
         // Test Case 4: '7' -> '1' (remove char)
         let seven_pattern = SEVEN_PATTERN;
         let one_off_patterns_from_seven = generate_one_off_patterns(seven_pattern);
-        assert!(one_off_patterns_from_seven.contains(&('1' as char)));
+        assert!(one_off_patterns_from_seven.contains(&'1'));
 
         // Test Case 5: '5' -> '6' and '9' (multiple candidates)
         let five_pattern = FIVE_PATTERN;
         let one_off_patterns_from_five = generate_one_off_patterns(five_pattern);
-        assert!(one_off_patterns_from_five.contains(&('6' as char)));
-        assert!(one_off_patterns_from_five.contains(&('9' as char)));
+        assert!(one_off_patterns_from_five.contains(&'6'));
+        assert!(one_off_patterns_from_five.contains(&'9'));
     }
 }
 
@@ -264,3 +302,35 @@ mod tests_correct_account_number_patterns {
         assert!(result.contains(&"490067719".to_string()));
     }
 }
+
+#[cfg(test)]
+mod tests_recognizer_properties {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::renderer::render_account_number;
+    use crate::resolver::resolve_account_number;
+
+    proptest! {
+        #[test]
+        fn render_then_recognize(account_number in "[0-9]{9}") {
+            let pattern = render_account_number(&account_number).unwrap();
+            prop_assert_eq!(account_number, recognize_account_number(&pattern));
+        }
+
+        #[test]
+        fn arbitrary_text_does_not_panic(text in "(?s).{0,120}", index in 0..12usize) {
+            cat_number(&text, index);
+            let account_number = recognize_account_number(&text);
+            prop_assert_eq!(9, account_number.chars().count());
+            resolve_account_number(&text);
+        }
+
+        #[test]
+        fn scanner_like_text_does_not_panic(lines in prop::collection::vec("[ _|]{0,30}", 0..5)) {
+            let text = lines.join("\n");
+            prop_assert_eq!(9, recognize_account_number(&text).chars().count());
+            resolve_account_number(&text);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::resolver::Resolution;
+
+/// The account numbers known to exist.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountRegistry {
+    accounts: HashSet<String>,
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Io(io::Error),
+    /// A line holds something other than an account number of the right length.
+    InvalidAccount {
+        line_number: usize,
+        account: String,
+    },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Io(e) => write!(f, "could not read registry: {e}"),
+            RegistryError::InvalidAccount {
+                line_number,
+                account,
+            } => write!(f, "line {line_number}: invalid account number {account:?}"),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RegistryError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RegistryError {
+    fn from(e: io::Error) -> Self {
+        RegistryError::Io(e)
+    }
+}
+
+/// How an entry relates to the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegistryMatch {
+    /// The account number is registered.
+    Registered,
+    /// Exactly one alternative of an ambiguous entry is registered, and was chosen.
+    ResolvedAmbiguity,
+    /// Several alternatives of an ambiguous entry are registered; only those are kept.
+    NarrowedAmbiguity,
+    /// Neither the number nor any alternative is registered.
+    Unregistered,
+}
+
+impl RegistryMatch {
+    pub fn label(self) -> &'static str {
+        match self {
+            RegistryMatch::Registered => "registered",
+            RegistryMatch::ResolvedAmbiguity => "resolved-by-registry",
+            RegistryMatch::NarrowedAmbiguity => "narrowed-by-registry",
+            RegistryMatch::Unregistered => "unregistered",
+        }
+    }
+}
+
+impl AccountRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads one account number of `length` digits per line. Blank lines and
+    /// lines starting with `#` are skipped, and spaces around numbers are ignored.
+    pub fn from_reader<R: BufRead>(reader: R, length: usize) -> Result<Self, RegistryError> {
+        let mut registry = Self::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let account = line.trim();
+            if account.is_empty() || account.starts_with('#') {
+                continue;
+            }
+            if account.len() != length || !account.chars().all(|c| c.is_ascii_digit()) {
+                return Err(RegistryError::InvalidAccount {
+                    line_number: i + 1,
+                    account: account.to_string(),
+                });
+            }
+            registry.insert(account);
+        }
+        Ok(registry)
+    }
+
+    pub fn load(path: impl AsRef<Path>, length: usize) -> Result<Self, RegistryError> {
+        Self::from_reader(BufReader::new(File::open(path)?), length)
+    }
+
+    pub fn insert(&mut self, account_number: &str) {
+        self.accounts.insert(account_number.to_string());
+    }
+
+    pub fn contains(&self, account_number: &str) -> bool {
+        self.accounts.contains(account_number)
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Whether `account_number` is registered.
+    pub fn check(&self, account_number: &str) -> RegistryMatch {
+        if self.contains(account_number) {
+            RegistryMatch::Registered
+        } else {
+            RegistryMatch::Unregistered
+        }
+    }
+
+    /// Drops the alternatives of an ambiguous resolution that are not registered.
+    /// A single registered alternative becomes `Corrected`; when none is registered
+    /// the alternatives are kept, since the registry cannot tell them apart.
+    /// An illegible number cannot be looked up and gets no match.
+    pub fn filter(&self, resolution: Resolution) -> (Resolution, Option<RegistryMatch>) {
+        match resolution {
+            Resolution::Ambiguous {
+                account_number,
+                alternatives,
+            } => {
+                let mut registered: Vec<String> = alternatives
+                    .iter()
+                    .filter(|a| self.contains(a))
+                    .cloned()
+                    .collect();
+                match registered.len() {
+                    0 => (
+                        Resolution::Ambiguous {
+                            account_number,
+                            alternatives,
+                        },
+                        Some(RegistryMatch::Unregistered),
+                    ),
+                    1 => (
+                        Resolution::Corrected(registered.remove(0)),
+                        Some(RegistryMatch::ResolvedAmbiguity),
+                    ),
+                    _ => (
+                        Resolution::Ambiguous {
+                            account_number,
+                            alternatives: registered,
+                        },
+                        Some(RegistryMatch::NarrowedAmbiguity),
+                    ),
+                }
+            }
+            Resolution::Valid(n) => {
+                let check = self.check(&n);
+                (Resolution::Valid(n), Some(check))
+            }
+            Resolution::Corrected(n) => {
+                let check = self.check(&n);
+                (Resolution::Corrected(n), Some(check))
+            }
+            Resolution::Illegible(n) => (Resolution::Illegible(n), None),
+        }
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for AccountRegistry {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut registry = Self::new();
+        for account_number in iter {
+            registry.insert(account_number.as_ref());
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests_account_registry {
+    use super::*;
+    use crate::checksum::AccountFormat;
+    use crate::parser::parse_entries;
+    use crate::report::{ReportStatus, report_entry_with_registry};
+    use crate::search::SearchOptions;
+
+    fn ambiguous() -> Resolution {
+        Resolution::Ambiguous {
+            account_number: "490067715".to_string(),
+            alternatives: vec![
+                "490067115".to_string(),
+                "490067719".to_string(),
+                "490867715".to_string(),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let registry =
+            AccountRegistry::from_reader("# known\n490867715\n\n  123456789 \n".as_bytes(), 9)
+                .unwrap();
+        assert_eq!(2, registry.len());
+        assert!(registry.contains("123456789"));
+        assert!(matches!(
+            AccountRegistry::from_reader("490867715\n49086771x\n".as_bytes(), 9),
+            Err(RegistryError::InvalidAccount { line_number: 2, .. })
+        ));
+        assert!(matches!(
+            AccountRegistry::from_reader("490867715\n4908677150\n".as_bytes(), 9),
+            Err(RegistryError::InvalidAccount { line_number: 2, .. })
+        ));
+        assert!(AccountRegistry::from_reader("2363\n".as_bytes(), 4).is_ok());
+    }
+
+    #[test]
+    fn test_filter() {
+        let registry: AccountRegistry = ["490867715"].into_iter().collect();
+        assert_eq!(
+            (
+                Resolution::Corrected("490867715".to_string()),
+                Some(RegistryMatch::ResolvedAmbiguity)
+            ),
+            registry.filter(ambiguous())
+        );
+
+        let registry: AccountRegistry = ["490067115", "490867715"].into_iter().collect();
+        let (resolution, check) = registry.filter(ambiguous());
+        assert_eq!(Some(RegistryMatch::NarrowedAmbiguity), check);
+        assert!(
+            matches!(resolution, Resolution::Ambiguous { alternatives, .. } if alternatives.len() == 2)
+        );
+
+        assert_eq!(
+            (ambiguous(), Some(RegistryMatch::Unregistered)),
+            AccountRegistry::new().filter(ambiguous())
+        );
+        assert_eq!(
+            Some(RegistryMatch::Registered),
+            registry
+                .filter(Resolution::Valid("490067115".to_string()))
+                .1
+        );
+        assert_eq!(
+            None,
+            registry
+                .filter(Resolution::Illegible("49006771?".to_string()))
+                .1
+        );
+    }
+
+    #[test]
+    fn test_report_entry_with_registry() {
+        let file = "    _  _  _  _  _  _     _ \n|_||_|| || ||_   |  |  ||_ \n  | _||_||_||_|  |  |  | _|\n";
+        let entry = parse_entries(file.as_bytes()).next().unwrap().unwrap();
+        let format = AccountFormat::kata();
+        let search = SearchOptions::default();
+        let registry: AccountRegistry = ["490867715"].into_iter().collect();
+
+        let report =
+            report_entry_with_registry(&entry, None, &format, Some(&search), Some(&registry));
+        assert_eq!(ReportStatus::Corrected, report.status);
+        assert_eq!("490867715", report.account_number);
+        assert_eq!(Some(RegistryMatch::ResolvedAmbiguity), report.registry);
+
+        let unchecked = report_entry_with_registry(&entry, None, &format, None, Some(&registry));
+        assert_eq!(ReportStatus::Err, unchecked.status);
+        assert_eq!(Some(RegistryMatch::Unregistered), unchecked.registry);
+
+        let illegible = parse_entries(
+            "    _  _     _  _  _  _  _ \n _| _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n"
+                .as_bytes(),
+        )
+        .next()
+        .unwrap()
+        .unwrap();
+        let unchecked =
+            report_entry_with_registry(&illegible, None, &format, None, Some(&registry));
+        assert_eq!(ReportStatus::Ill, unchecked.status);
+        assert_eq!(None, unchecked.registry);
+    }
+}
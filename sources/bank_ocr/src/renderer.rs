@@ -0,0 +1,162 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::glyph_table::GlyphTable;
+use crate::layout::Layout;
+
+#[derive(Debug)]
+pub enum RenderError {
+    UnknownCharacter(char),
+    Io(io::Error),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::UnknownCharacter(c) => write!(f, "no glyph for {c:?}"),
+            RenderError::Io(e) => write!(f, "could not write scanner file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(e: io::Error) -> Self {
+        RenderError::Io(e)
+    }
+}
+
+/// Renders an account number as a 4-line scanner entry, the inverse of
+/// `recognizer::recognize_account_number`.
+pub fn render_account_number(account_number: &str) -> Result<String, RenderError> {
+    render_with_glyphs(account_number, &GlyphTable::digits())
+}
+
+/// Renders any number of characters with the glyphs of `table`.
+/// Each line is three columns per character, and the 4th line is blank.
+pub fn render_with_glyphs(text: &str, table: &GlyphTable) -> Result<String, RenderError> {
+    render_with_layout(text, table, &Layout::kata())
+}
+
+/// Renders the glyphs of `table` in `layout`, followed by a blank line when
+/// the layout has a separator.
+pub fn render_with_layout(
+    text: &str,
+    table: &GlyphTable,
+    layout: &Layout,
+) -> Result<String, RenderError> {
+    let mut lines = vec![String::new(); layout.glyph_height];
+    for c in text.chars() {
+        let segments = table.segments(c).ok_or(RenderError::UnknownCharacter(c))?;
+        for (line, row) in lines.iter_mut().zip(layout.draw(segments)) {
+            line.push_str(&row);
+        }
+    }
+    if layout.separator {
+        lines.push(" ".repeat(layout.width(text.chars().count())));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Writes a scanner file holding one entry per account number.
+pub fn write_scanner_file<W, I, S>(
+    writer: &mut W,
+    account_numbers: I,
+    table: &GlyphTable,
+) -> Result<(), RenderError>
+where
+    W: Write,
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    write_scanner_file_with(writer, account_numbers, table, &Layout::kata())
+}
+
+/// Like `write_scanner_file`, with the entries drawn in `layout`.
+pub fn write_scanner_file_with<W, I, S>(
+    writer: &mut W,
+    account_numbers: I,
+    table: &GlyphTable,
+    layout: &Layout,
+) -> Result<(), RenderError>
+where
+    W: Write,
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    for account_number in account_numbers {
+        writeln!(
+            writer,
+            "{}",
+            render_with_layout(account_number.as_ref(), table, layout)?
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_render_account_number {
+    use super::*;
+    use crate::parser::parse_entries;
+    use crate::recognizer::recognize_account_number;
+    use crate::segments::DIGIT_SEGMENTS;
+
+    #[test]
+    fn test_render_123456789() {
+        let expected = "    _  _     _  _  _  _  _ \n  | _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n                           ";
+        assert_eq!(expected, render_account_number("123456789").unwrap());
+    }
+
+    #[test]
+    fn test_render_then_recognize() {
+        for account_number in ["000000000", "457508000", "664371495", "490867715"] {
+            let pattern = render_account_number(account_number).unwrap();
+            assert_eq!(account_number, recognize_account_number(&pattern));
+        }
+    }
+
+    #[test]
+    fn test_render_other_digit_count() {
+        let pattern = render_account_number("10").unwrap();
+        assert_eq!("    _ \n  || |\n  ||_|\n      ", pattern);
+    }
+
+    #[test]
+    fn test_render_unknown_character() {
+        assert!(matches!(
+            render_account_number("86110??36"),
+            Err(RenderError::UnknownCharacter('?'))
+        ));
+    }
+
+    #[test]
+    fn test_render_custom_glyphs() {
+        let mut table = GlyphTable::new();
+        table.insert('I', DIGIT_SEGMENTS[1]);
+        table.insert('T', DIGIT_SEGMENTS[7]);
+        assert_eq!(
+            render_account_number("17").unwrap(),
+            render_with_glyphs("IT", &table).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_scanner_file_round_trip() {
+        let account_numbers = ["457508000", "664371495", "123456789"];
+        let mut file = Vec::new();
+        write_scanner_file(&mut file, account_numbers, &GlyphTable::digits()).unwrap();
+
+        let recognized: Vec<String> = parse_entries(file.as_slice())
+            .map(|entry| recognize_account_number(&entry.unwrap().text()))
+            .collect();
+        assert_eq!(account_numbers.to_vec(), recognized);
+    }
+}
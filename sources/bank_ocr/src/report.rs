@@ -0,0 +1,235 @@
+use crate::account_number::{AccountNumber, Status};
+use crate::checksum::AccountFormat;
+use crate::normalize::Normalization;
+use crate::parser::Entry;
+use crate::registry::{AccountRegistry, RegistryMatch};
+use crate::resolver::{
+    ChecksumEvidence, Resolution, checksum_suggestion, resolve_account_number_with_checksum,
+};
+use crate::scoring::score_entry;
+use crate::search::SearchOptions;
+
+/// The status of one entry in a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportStatus {
+    Ok,
+    /// Valid after a correction.
+    Corrected,
+    Err,
+    Ill,
+    Amb,
+    /// Decided by a reviewer, see `review`.
+    Reviewed,
+}
+
+impl ReportStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            ReportStatus::Ok => "OK",
+            ReportStatus::Corrected => "CORRECTED",
+            ReportStatus::Err => "ERR",
+            ReportStatus::Ill => "ILL",
+            ReportStatus::Amb => "AMB",
+            ReportStatus::Reviewed => "REVIEWED",
+        }
+    }
+}
+
+/// Everything known about one processed entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryReport {
+    /// The scanner file the entry was read from, if any.
+    pub source: Option<String>,
+    pub index: usize,
+    pub line_number: usize,
+    /// The number as recognized, with its glyphs.
+    pub raw: AccountNumber,
+    /// The number after correction.
+    pub account_number: String,
+    pub status: ReportStatus,
+    /// The candidates of an `Amb` entry, sorted, or the number the checksum
+    /// suggests for an `Ill` entry.
+    pub alternatives: Vec<String>,
+    /// The confidence of every recognized digit, see `scoring`.
+    pub confidences: Vec<f64>,
+    /// What was changed to read the entry, see `normalize`.
+    pub normalizations: Vec<Normalization>,
+    /// The lines of the entry as read, after normalization.
+    pub lines: Vec<String>,
+    /// How the number relates to the registry, when one is used.
+    pub registry: Option<RegistryMatch>,
+    /// How the checksum helped with a single illegible digit, see
+    /// `resolver::resolve_account_number_with_checksum`.
+    pub checksum: Option<ChecksumEvidence>,
+}
+
+/// The number of entries per status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub ok: usize,
+    pub corrected: usize,
+    pub err: usize,
+    pub ill: usize,
+    pub amb: usize,
+    pub reviewed: usize,
+    /// Entries that could not be parsed and were skipped.
+    pub broken: usize,
+}
+
+impl Summary {
+    pub fn total(&self) -> usize {
+        self.ok + self.corrected + self.err + self.ill + self.amb + self.reviewed + self.broken
+    }
+
+    pub fn record(&mut self, status: ReportStatus) {
+        match status {
+            ReportStatus::Ok => self.ok += 1,
+            ReportStatus::Corrected => self.corrected += 1,
+            ReportStatus::Err => self.err += 1,
+            ReportStatus::Ill => self.ill += 1,
+            ReportStatus::Amb => self.amb += 1,
+            ReportStatus::Reviewed => self.reviewed += 1,
+        }
+    }
+
+    /// Adds the counts of `other`, e.g. to total several files.
+    pub fn add(&mut self, other: &Summary) {
+        self.ok += other.ok;
+        self.corrected += other.corrected;
+        self.err += other.err;
+        self.ill += other.ill;
+        self.amb += other.amb;
+        self.reviewed += other.reviewed;
+        self.broken += other.broken;
+    }
+}
+
+/// Recognizes, validates and, when `search` is given, corrects one entry.
+pub fn report_entry(
+    entry: &Entry,
+    source: Option<&str>,
+    format: &AccountFormat,
+    search: Option<&SearchOptions>,
+) -> EntryReport {
+    report_entry_with_registry(entry, source, format, search, None)
+}
+
+/// Like `report_entry`, but drops the alternatives that are not in `registry`
+/// and flags numbers that are not registered.
+pub fn report_entry_with_registry(
+    entry: &Entry,
+    source: Option<&str>,
+    format: &AccountFormat,
+    search: Option<&SearchOptions>,
+    registry: Option<&AccountRegistry>,
+) -> EntryReport {
+    let text = entry.text();
+    let raw = AccountNumber::recognize(&text, format);
+    let confidences = score_entry(&text, format).confidences();
+
+    let mut registry_match = None;
+    let mut checksum = None;
+    let (account_number, status, alternatives) = match search {
+        Some(search) => {
+            let (mut resolution, evidence) =
+                resolve_account_number_with_checksum(&text, format, search);
+            checksum = evidence;
+            if let Some(registry) = registry {
+                let (filtered, check) = registry.filter(resolution);
+                resolution = filtered;
+                registry_match = check;
+            }
+            match resolution {
+                Resolution::Valid(n) => (n, ReportStatus::Ok, Vec::new()),
+                Resolution::Corrected(n) => (n, ReportStatus::Corrected, Vec::new()),
+                Resolution::Ambiguous {
+                    account_number,
+                    alternatives,
+                } => (account_number, ReportStatus::Amb, alternatives),
+                Resolution::Illegible(n) => {
+                    // チェックサムだけで決まった番号は確認用の候補にする
+                    let alternatives = match checksum {
+                        Some(ChecksumEvidence::Determined) => {
+                            checksum_suggestion(&n, format).into_iter().collect()
+                        }
+                        _ => Vec::new(),
+                    };
+                    (n, ReportStatus::Ill, alternatives)
+                }
+            }
+        }
+        None => {
+            if raw.is_legible() {
+                registry_match = registry.map(|r| r.check(&raw.number()));
+            }
+            let status = match raw.status_in(format) {
+                Status::Ok => ReportStatus::Ok,
+                Status::Ill => ReportStatus::Ill,
+                _ => ReportStatus::Err,
+            };
+            (raw.number(), status, Vec::new())
+        }
+    };
+
+    EntryReport {
+        source: source.map(String::from),
+        index: entry.index,
+        line_number: entry.line_number,
+        raw,
+        account_number,
+        status,
+        alternatives,
+        confidences,
+        normalizations: entry.normalizations.clone(),
+        lines: entry.lines.clone(),
+        registry: registry_match,
+        checksum,
+    }
+}
+
+#[cfg(test)]
+mod tests_report {
+    use super::*;
+
+    #[test]
+    fn test_report_entry() {
+        use crate::parser::parse_entries;
+
+        let file = "    _  _  _  _  _  _     _ \n|_||_|| || ||_   |  |  ||_ \n  | _||_||_||_|  |  |  | _|\n\n    _  _     _  _  _  _  _ \n _| _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n";
+        let entries: Vec<Entry> = parse_entries(file.as_bytes()).map(Result::unwrap).collect();
+        let format = AccountFormat::kata();
+        let search = SearchOptions::default();
+
+        let ambiguous = report_entry(&entries[0], Some("scan.txt"), &format, Some(&search));
+        assert_eq!(Some("scan.txt".to_string()), ambiguous.source);
+        assert_eq!((0, 1), (ambiguous.index, ambiguous.line_number));
+        assert_eq!("490067715", ambiguous.raw.number());
+        assert_eq!(ReportStatus::Amb, ambiguous.status);
+        assert_eq!(
+            vec!["490067115", "490067719", "490867715"],
+            ambiguous.alternatives
+        );
+        assert_eq!(9, ambiguous.confidences.len());
+
+        let corrected = report_entry(&entries[1], None, &format, Some(&search));
+        assert_eq!((1, 5), (corrected.index, corrected.line_number));
+        assert_eq!("?23456789", corrected.raw.number());
+        assert_eq!("123456789", corrected.account_number);
+        assert_eq!(ReportStatus::Corrected, corrected.status);
+        assert!(corrected.confidences[0] < corrected.confidences[1]);
+        assert_eq!(Some(ChecksumEvidence::Agrees), corrected.checksum);
+        assert_eq!(None, ambiguous.checksum);
+
+        let illegible = report_entry(&entries[1], None, &format, None);
+        assert_eq!(ReportStatus::Ill, illegible.status);
+        assert_eq!("?23456789", illegible.account_number);
+
+        let mut summary = Summary::default();
+        summary.record(ambiguous.status);
+        summary.record(corrected.status);
+        assert_eq!((1, 1), (summary.amb, summary.corrected));
+        let mut total = summary;
+        total.add(&summary);
+        assert_eq!(4, total.total());
+    }
+}
@@ -0,0 +1,412 @@
+use crate::account_number::AccountNumber;
+use crate::checksum::AccountFormat;
+use crate::recognizer::generate_one_off_patterns;
+use crate::search::{SearchOptions, search_corrections};
+use crate::validator::MissingDigit;
+
+/// The final decision for one scanned entry (User Story 4).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// The scanned number has a valid checksum.
+    Valid(String),
+    /// Exactly one number within one pipe or underscore of the scan has a valid checksum.
+    Corrected(String),
+    /// Several numbers could be meant; `alternatives` is sorted.
+    Ambiguous {
+        account_number: String,
+        alternatives: Vec<String>,
+    },
+    /// No number within one pipe or underscore of the scan has a valid checksum.
+    Illegible(String),
+}
+
+pub fn resolve_account_number(pattern: &str) -> Resolution {
+    resolve_account_number_with(pattern, &AccountFormat::kata())
+}
+
+pub fn resolve_account_number_with(pattern: &str, format: &AccountFormat) -> Resolution {
+    let recognized = AccountNumber::recognize(pattern, format);
+    let account_number = recognized.number();
+    if recognized.is_valid_in(format) {
+        return Resolution::Valid(account_number);
+    }
+
+    let illegible_positions = recognized.illegible_positions();
+    // 1本の線の追加・削除で直せるのは1桁だけなので、? が2つ以上あれば候補はない
+    let positions: Vec<usize> = match illegible_positions.len() {
+        0 => (0..recognized.digits().len()).collect(),
+        1 => illegible_positions,
+        _ => Vec::new(),
+    };
+
+    let mut alternatives = candidates_at(pattern, &account_number, &positions, format);
+    alternatives.sort();
+    alternatives.dedup();
+    match alternatives.len() {
+        0 => Resolution::Illegible(account_number),
+        1 => Resolution::Corrected(alternatives.remove(0)),
+        _ => Resolution::Ambiguous {
+            account_number,
+            alternatives,
+        },
+    }
+}
+
+/// Resolves with `search::search_corrections`, allowing up to `options.max_edits`
+/// changed cells. Only the candidates with the fewest edits are kept, so with the
+/// default options this agrees with `resolve_account_number_with`.
+pub fn resolve_account_number_searching(
+    pattern: &str,
+    format: &AccountFormat,
+    options: &SearchOptions,
+) -> Resolution {
+    let recognized = AccountNumber::recognize(pattern, format);
+    let account_number = recognized.number();
+    if recognized.is_valid_in(format) {
+        return Resolution::Valid(account_number);
+    }
+
+    let candidates = search_corrections(pattern, format, options);
+    let Some(fewest_edits) = candidates.first().map(|c| c.edits) else {
+        return Resolution::Illegible(account_number);
+    };
+    let mut alternatives: Vec<String> = candidates
+        .into_iter()
+        .filter(|c| c.edits == fewest_edits)
+        .map(|c| c.account_number)
+        .collect();
+    alternatives.sort();
+    match alternatives.len() {
+        1 => Resolution::Corrected(alternatives.remove(0)),
+        _ => Resolution::Ambiguous {
+            account_number,
+            alternatives,
+        },
+    }
+}
+
+/// How the checksum took part in resolving a number with one illegible digit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumEvidence {
+    /// The segment search found the digit the checksum requires.
+    Agrees,
+    /// No digit was within reach of the scanned glyph; the checksum alone
+    /// suggests one. The entry stays illegible, see `checksum_suggestion`.
+    Determined,
+    /// The segments point to other numbers; the checksum's was added to them.
+    Conflicts,
+    /// The checksum allows no digit, or every digit, at the position.
+    Inconclusive,
+}
+
+impl ChecksumEvidence {
+    pub fn label(self) -> &'static str {
+        match self {
+            ChecksumEvidence::Agrees => "agrees",
+            ChecksumEvidence::Determined => "solved-by-checksum",
+            ChecksumEvidence::Conflicts => "conflicts",
+            ChecksumEvidence::Inconclusive => "inconclusive",
+        }
+    }
+}
+
+/// Like `resolve_account_number_searching`, but a number with a single `?` is
+/// also solved for that digit when the checksum allows it (see
+/// `Checksum::solve_missing_digit`), and both are combined. A digit found by
+/// the checksum alone is not read: the entry stays `Illegible`. The evidence
+/// is `None` when the checksum was not used.
+pub fn resolve_account_number_with_checksum(
+    pattern: &str,
+    format: &AccountFormat,
+    options: &SearchOptions,
+) -> (Resolution, Option<ChecksumEvidence>) {
+    let resolution = resolve_account_number_searching(pattern, format, options);
+    let account_number = AccountNumber::recognize(pattern, format).number();
+    let Some(missing) = format.checksum.solve_missing_digit(&account_number) else {
+        return (resolution, None);
+    };
+    let MissingDigit::Digit(digit) = missing else {
+        return (resolution, Some(ChecksumEvidence::Inconclusive));
+    };
+    let solved = account_number.replacen('?', &digit.to_string(), 1);
+
+    match resolution {
+        Resolution::Corrected(n) if n == solved => {
+            (Resolution::Corrected(n), Some(ChecksumEvidence::Agrees))
+        }
+        Resolution::Illegible(n) => (Resolution::Illegible(n), Some(ChecksumEvidence::Determined)),
+        Resolution::Corrected(n) => {
+            let mut alternatives = vec![n, solved];
+            alternatives.sort();
+            (
+                Resolution::Ambiguous {
+                    account_number,
+                    alternatives,
+                },
+                Some(ChecksumEvidence::Conflicts),
+            )
+        }
+        Resolution::Ambiguous {
+            account_number,
+            mut alternatives,
+        } => {
+            let evidence = if alternatives.contains(&solved) {
+                ChecksumEvidence::Agrees
+            } else {
+                alternatives.push(solved);
+                alternatives.sort();
+                ChecksumEvidence::Conflicts
+            };
+            (
+                Resolution::Ambiguous {
+                    account_number,
+                    alternatives,
+                },
+                Some(evidence),
+            )
+        }
+        // ? を含む番号は有効にならない
+        Resolution::Valid(n) => (Resolution::Valid(n), None),
+    }
+}
+
+/// The number the checksum gives for the single `?` of `account_number`, when
+/// exactly one digit makes it valid.
+pub fn checksum_suggestion(account_number: &str, format: &AccountFormat) -> Option<String> {
+    match format.checksum.solve_missing_digit(account_number)? {
+        MissingDigit::Digit(digit) => Some(account_number.replacen('?', &digit.to_string(), 1)),
+        _ => None,
+    }
+}
+
+fn candidates_at(
+    pattern: &str,
+    account_number: &str,
+    positions: &[usize],
+    format: &AccountFormat,
+) -> Vec<String> {
+    let chars: Vec<char> = account_number.chars().collect();
+    let mut candidates = Vec::new();
+    for &i in positions {
+        for digit in generate_one_off_patterns(format.layout.cut(pattern, i)) {
+            if digit == chars[i] {
+                continue;
+            }
+            let mut candidate = chars.clone();
+            candidate[i] = digit;
+            let candidate: String = candidate.into_iter().collect();
+            if format.is_valid(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests_resolve_account_number {
+    use super::*;
+
+    fn ambiguous(account_number: &str, alternatives: &[&str]) -> Resolution {
+        Resolution::Ambiguous {
+            account_number: account_number.to_string(),
+            alternatives: alternatives.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_valid() {
+        let pattern = "    _  _     _  _  _  _  _ \n  | _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n                           ";
+        assert_eq!(
+            Resolution::Valid("123456789".to_string()),
+            resolve_account_number(pattern)
+        );
+    }
+
+    #[test]
+    fn test_resolve_illegible() {
+        let pattern = "    _  _     _  _  _  _  _ \n  | _| _||_| _ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _ \n                           ";
+        assert_eq!(
+            Resolution::Illegible("1234?678?".to_string()),
+            resolve_account_number(pattern)
+        );
+    }
+
+    #[test]
+    fn test_resolve_with_other_format() {
+        use crate::checksum::Luhn;
+        use crate::renderer::render_account_number;
+
+        // 79927398713 の3桁目の 9 に線が1本多く読まれて 8 に見えるもの
+        let pattern = render_account_number("79827398713").unwrap();
+        let format = AccountFormat::new(11, Box::new(Luhn));
+        assert_eq!(
+            Resolution::Corrected("79927398713".to_string()),
+            resolve_account_number_with(&pattern, &format)
+        );
+    }
+
+    // doc/bank_ocr.adoc の use case 4
+    #[test]
+    fn test_resolve_use_case_4() {
+        let test_cases = [
+            (
+                "                           \n  |  |  |  |  |  |  |  |  |\n  |  |  |  |  |  |  |  |  |\n                           ",
+                Resolution::Corrected("711111111".to_string()),
+            ),
+            (
+                " _  _  _  _  _  _  _  _  _ \n  |  |  |  |  |  |  |  |  |\n  |  |  |  |  |  |  |  |  |\n                           ",
+                Resolution::Corrected("777777177".to_string()),
+            ),
+            (
+                " _  _  _  _  _  _  _  _  _ \n _|| || || || || || || || |\n|_ |_||_||_||_||_||_||_||_|\n                           ",
+                Resolution::Corrected("200800000".to_string()),
+            ),
+            (
+                " _  _  _  _  _  _  _  _  _ \n _| _| _| _| _| _| _| _| _|\n _| _| _| _| _| _| _| _| _|\n                           ",
+                Resolution::Corrected("333393333".to_string()),
+            ),
+            (
+                " _  _  _  _  _  _  _  _  _ \n|_||_||_||_||_||_||_||_||_|\n|_||_||_||_||_||_||_||_||_|\n                           ",
+                ambiguous("888888888", &["888886888", "888888880", "888888988"]),
+            ),
+            (
+                " _  _  _  _  _  _  _  _  _ \n|_ |_ |_ |_ |_ |_ |_ |_ |_ \n _| _| _| _| _| _| _| _| _|\n                           ",
+                ambiguous("555555555", &["555655555", "559555555"]),
+            ),
+            (
+                " _  _  _  _  _  _  _  _  _ \n|_ |_ |_ |_ |_ |_ |_ |_ |_ \n|_||_||_||_||_||_||_||_||_|\n                           ",
+                ambiguous("666666666", &["666566666", "686666666"]),
+            ),
+            (
+                " _  _  _  _  _  _  _  _  _ \n|_||_||_||_||_||_||_||_||_|\n _| _| _| _| _| _| _| _| _|\n                           ",
+                ambiguous("999999999", &["899999999", "993999999", "999959999"]),
+            ),
+            (
+                "    _  _  _  _  _  _     _ \n|_||_|| || ||_   |  |  ||_ \n  | _||_||_||_|  |  |  | _|\n                           ",
+                ambiguous("490067715", &["490067115", "490067719", "490867715"]),
+            ),
+            (
+                "    _  _     _  _  _  _  _ \n _| _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n                           ",
+                Resolution::Corrected("123456789".to_string()),
+            ),
+            (
+                " _     _  _  _  _  _  _    \n| || || || || || || ||_   |\n|_||_||_||_||_||_||_| _|  |\n                           ",
+                Resolution::Corrected("000000051".to_string()),
+            ),
+            (
+                "    _  _  _  _  _  _     _ \n|_||_|| ||_||_   |  |  | _ \n  | _||_||_||_|  |  |  | _|\n                           ",
+                Resolution::Corrected("490867715".to_string()),
+            ),
+        ];
+
+        for (pattern, expected) in test_cases.iter() {
+            assert_eq!(*expected, resolve_account_number(pattern), "{pattern}");
+            assert_eq!(
+                *expected,
+                resolve_account_number_searching(
+                    pattern,
+                    &AccountFormat::kata(),
+                    &SearchOptions::default()
+                ),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_searching_two_edits() {
+        // 000000051 の 0 から線が2本欠けたもの
+        let pattern = " _     _  _  _  _  _  _    \n| |  || || || || || ||_   |\n|_||_||_||_||_||_||_| _|  |\n                           ";
+        let format = AccountFormat::kata();
+        assert_eq!(
+            Resolution::Illegible("0?0000051".to_string()),
+            resolve_account_number_searching(pattern, &format, &SearchOptions::default())
+        );
+        let options = SearchOptions {
+            max_edits: 2,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            Resolution::Corrected("000000051".to_string()),
+            resolve_account_number_searching(pattern, &format, &options)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_resolve_with_checksum {
+    use super::*;
+    use crate::checksum::{Luhn, Mod11};
+    use crate::renderer::render_account_number;
+
+    #[test]
+    fn test_agrees_with_segments() {
+        let pattern = "    _  _     _  _  _  _  _ \n _| _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n                           ";
+        assert_eq!(
+            (
+                Resolution::Corrected("123456789".to_string()),
+                Some(ChecksumEvidence::Agrees)
+            ),
+            resolve_account_number_with_checksum(
+                pattern,
+                &AccountFormat::kata(),
+                &SearchOptions::default()
+            )
+        );
+    }
+
+    #[test]
+    fn test_determined_by_checksum() {
+        // 0 から線が2本欠けていて、1本の訂正では届かない。読めない桁は ILL のまま
+        let pattern = " _     _  _  _  _  _  _    \n| |  || || || || || ||_   |\n|_||_||_||_||_||_||_| _|  |\n                           ";
+        let format = AccountFormat::kata();
+        assert_eq!(
+            (
+                Resolution::Illegible("0?0000051".to_string()),
+                Some(ChecksumEvidence::Determined)
+            ),
+            resolve_account_number_with_checksum(pattern, &format, &SearchOptions::default())
+        );
+        assert_eq!(
+            Some("000000051".to_string()),
+            checksum_suggestion("0?0000051", &format)
+        );
+        assert_eq!(None, checksum_suggestion("50000000?", &format));
+    }
+
+    #[test]
+    fn test_underdetermined() {
+        // 11 桁の先頭の重みは 11 なので、チェックサムでは決まらない
+        let mut lines: Vec<String> = render_account_number("10000000000")
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        lines[0].replace_range(0..3, "|||");
+        let pattern = lines.join("\n");
+        assert_eq!(
+            (
+                Resolution::Illegible("?0000000000".to_string()),
+                Some(ChecksumEvidence::Inconclusive)
+            ),
+            resolve_account_number_with_checksum(
+                &pattern,
+                &AccountFormat::new(11, Box::new(Mod11)),
+                &SearchOptions::default()
+            )
+        );
+    }
+
+    #[test]
+    fn test_other_checksums_are_not_solved() {
+        let pattern = " _     _  _  _  _  _  _    \n| |  || || || || || ||_   |\n|_||_||_||_||_||_||_| _|  |\n                           ";
+        let (resolution, evidence) = resolve_account_number_with_checksum(
+            pattern,
+            &AccountFormat::new(9, Box::new(Luhn)),
+            &SearchOptions::default(),
+        );
+        assert_eq!(Resolution::Illegible("0?0000051".to_string()), resolution);
+        assert_eq!(None, evidence);
+    }
+}
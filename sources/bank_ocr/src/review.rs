@@ -0,0 +1,587 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::checksum::AccountFormat;
+use crate::output::csv_field;
+use crate::report::{EntryReport, ReportStatus};
+
+/// An AMB or ILL entry waiting for a reviewer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewItem {
+    /// 1-based position in the queue, used to match decisions.
+    pub id: usize,
+    pub source: Option<String>,
+    pub index: usize,
+    pub line_number: usize,
+    pub status: ReportStatus,
+    pub raw: String,
+    pub account_number: String,
+    /// The alternatives of an AMB entry, or the checksum's suggestion for an
+    /// ILL entry.
+    pub candidates: Vec<String>,
+    /// The lines of the entry as scanned.
+    pub lines: Vec<String>,
+}
+
+/// What a reviewer decided for one item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewDecision {
+    /// The entry is this account number.
+    Accept(String),
+    /// The entry cannot be read; it keeps its status.
+    Reject,
+}
+
+impl fmt::Display for ReviewDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReviewDecision::Accept(account_number) => write!(f, "{account_number}"),
+            ReviewDecision::Reject => write!(f, "reject"),
+        }
+    }
+}
+
+/// One entry of the audit trail: an item that was decided by hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub id: usize,
+    pub source: Option<String>,
+    pub index: usize,
+    pub line_number: usize,
+    pub previous_status: ReportStatus,
+    pub previous_account_number: String,
+    pub decision: ReviewDecision,
+    /// Whether an accepted number passes the checksum.
+    pub checksum_valid: bool,
+}
+
+#[derive(Debug)]
+pub enum ReviewError {
+    Io(io::Error),
+    /// A line of a review file is not `key: value`, `> glyphs` or blank.
+    InvalidLine {
+        line_number: usize,
+    },
+    /// A decision is neither empty, `reject` nor a number.
+    InvalidDecision {
+        line_number: usize,
+        decision: String,
+    },
+    /// An accepted number does not have as many digits as the account format.
+    DecisionLength {
+        id: usize,
+        expected: usize,
+        length: usize,
+    },
+    /// A decision for an item that is not in the queue.
+    UnknownItem {
+        id: usize,
+    },
+    /// The entry of an item is not among the reports being merged.
+    MissingEntry {
+        id: usize,
+    },
+    /// The entry at the position of an item holds other glyphs, e.g. because
+    /// the scanner file changed since the queue was written.
+    EntryMismatch {
+        id: usize,
+    },
+}
+
+impl fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReviewError::Io(e) => write!(f, "could not read review file: {e}"),
+            ReviewError::InvalidLine { line_number } => {
+                write!(f, "line {line_number}: expected `key: value`")
+            }
+            ReviewError::InvalidDecision {
+                line_number,
+                decision,
+            } => write!(
+                f,
+                "line {line_number}: invalid decision {decision:?}, expected an account number or `reject`"
+            ),
+            ReviewError::DecisionLength {
+                id,
+                expected,
+                length,
+            } => write!(
+                f,
+                "review item {id}: expected {expected} digits but the decision has {length}"
+            ),
+            ReviewError::UnknownItem { id } => write!(f, "no review item {id}"),
+            ReviewError::MissingEntry { id } => {
+                write!(f, "the entry of review item {id} is not in the report")
+            }
+            ReviewError::EntryMismatch { id } => {
+                write!(f, "the entry of review item {id} does not match the report")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReviewError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReviewError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReviewError {
+    fn from(e: io::Error) -> Self {
+        ReviewError::Io(e)
+    }
+}
+
+/// The entries that need a human, in the order they were queued.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReviewQueue {
+    items: Vec<ReviewItem>,
+}
+
+impl ReviewQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `report` if it is AMB or ILL, and returns whether it was queued.
+    pub fn push(&mut self, report: &EntryReport) -> bool {
+        if !matches!(report.status, ReportStatus::Amb | ReportStatus::Ill) {
+            return false;
+        }
+        self.items.push(ReviewItem {
+            id: self.items.len() + 1,
+            source: report.source.clone(),
+            index: report.index,
+            line_number: report.line_number,
+            status: report.status,
+            raw: report.raw.number(),
+            account_number: report.account_number.clone(),
+            candidates: report.alternatives.clone(),
+            lines: report.lines.clone(),
+        });
+        true
+    }
+
+    pub fn items(&self) -> &[ReviewItem] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Writes the queue for a reviewer, who fills in the `decision:` lines with
+    /// the account number or `reject`, and leaves undecided items blank.
+    ///
+    /// ```text
+    /// # review 1
+    /// source: scan.txt
+    /// entry: 0
+    /// line: 1
+    /// status: AMB
+    /// raw: 490067715
+    /// account_number: 490067715
+    /// candidates: 490067115 490067719 490867715
+    /// >     _  _  _  _  _  _     _
+    /// > |_||_|| || ||_   |  |  ||_
+    /// >   | _||_||_||_|  |  |  | _|
+    /// decision:
+    /// ```
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            writeln!(writer, "# review {}", item.id)?;
+            if let Some(source) = &item.source {
+                writeln!(writer, "source: {source}")?;
+            }
+            writeln!(writer, "entry: {}", item.index)?;
+            writeln!(writer, "line: {}", item.line_number)?;
+            writeln!(writer, "status: {}", item.status.label())?;
+            writeln!(writer, "raw: {}", item.raw)?;
+            writeln!(writer, "account_number: {}", item.account_number)?;
+            writeln!(writer, "candidates: {}", item.candidates.join(" "))?;
+            for line in &item.lines {
+                writeln!(writer, "> {line}")?;
+            }
+            writeln!(writer, "decision:")?;
+        }
+        Ok(())
+    }
+
+    /// Reads a file written by `write_to`, with the reviewer's decisions.
+    /// Returns the queue and the decisions made, by item id.
+    pub fn from_reader<R: BufRead>(
+        reader: R,
+    ) -> Result<(Self, Vec<(usize, ReviewDecision)>), ReviewError> {
+        let mut queue = Self::new();
+        let mut decisions = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = i + 1;
+            let invalid = || ReviewError::InvalidLine { line_number };
+
+            if let Some(id) = line.strip_prefix("# review ") {
+                let id = id.trim().parse().map_err(|_| invalid())?;
+                queue.items.push(ReviewItem {
+                    id,
+                    source: None,
+                    index: 0,
+                    line_number: 0,
+                    status: ReportStatus::Ill,
+                    raw: String::new(),
+                    account_number: String::new(),
+                    candidates: Vec::new(),
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let item = queue.items.last_mut().ok_or_else(invalid)?;
+            if let Some(glyphs) = line.strip_prefix('>') {
+                item.lines
+                    .push(glyphs.strip_prefix(' ').unwrap_or(glyphs).to_string());
+                continue;
+            }
+
+            let (key, value) = line.split_once(':').ok_or_else(invalid)?;
+            let value = value.trim();
+            match key {
+                "source" => item.source = Some(value.to_string()),
+                "entry" => item.index = value.parse().map_err(|_| invalid())?,
+                "line" => item.line_number = value.parse().map_err(|_| invalid())?,
+                "status" => {
+                    item.status = match value {
+                        "AMB" => ReportStatus::Amb,
+                        "ILL" => ReportStatus::Ill,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "raw" => item.raw = value.to_string(),
+                "account_number" => item.account_number = value.to_string(),
+                "candidates" => {
+                    item.candidates = value.split_whitespace().map(String::from).collect()
+                }
+                "decision" => match value {
+                    "" => {}
+                    "reject" => decisions.push((item.id, ReviewDecision::Reject)),
+                    _ if value.chars().all(|c| c.is_ascii_digit()) => {
+                        decisions.push((item.id, ReviewDecision::Accept(value.to_string())))
+                    }
+                    _ => {
+                        return Err(ReviewError::InvalidDecision {
+                            line_number,
+                            decision: value.to_string(),
+                        });
+                    }
+                },
+                _ => return Err(invalid()),
+            }
+        }
+        Ok((queue, decisions))
+    }
+
+    /// Applies `decisions` to the matching `reports`: accepted numbers become
+    /// `Reviewed`, rejected entries keep their status. Returns the audit trail.
+    ///
+    /// An item matches the report at its source and index, which must hold the
+    /// same glyphs; trailing spaces, which editors tend to drop, are ignored.
+    /// Accepted numbers must have `format.length` digits. Every decision is
+    /// checked before any is applied, so `reports` is unchanged on error.
+    pub fn merge(
+        &self,
+        reports: &mut [EntryReport],
+        decisions: &[(usize, ReviewDecision)],
+        format: &AccountFormat,
+    ) -> Result<Vec<AuditRecord>, ReviewError> {
+        let mut matched = Vec::new();
+        for (id, decision) in decisions {
+            let item = self
+                .items
+                .iter()
+                .find(|item| item.id == *id)
+                .ok_or(ReviewError::UnknownItem { id: *id })?;
+            let position = reports
+                .iter()
+                .position(|r| r.source == item.source && r.index == item.index)
+                .ok_or(ReviewError::MissingEntry { id: *id })?;
+            let same_lines = item
+                .lines
+                .iter()
+                .map(|l| l.trim_end())
+                .eq(reports[position].lines.iter().map(|l| l.trim_end()));
+            if !same_lines {
+                return Err(ReviewError::EntryMismatch { id: *id });
+            }
+            if let ReviewDecision::Accept(n) = decision
+                && n.len() != format.length
+            {
+                return Err(ReviewError::DecisionLength {
+                    id: *id,
+                    expected: format.length,
+                    length: n.len(),
+                });
+            }
+            matched.push((item, position, decision));
+        }
+
+        let mut audit = Vec::new();
+        for (item, position, decision) in matched {
+            let report = &mut reports[position];
+            audit.push(AuditRecord {
+                id: item.id,
+                source: item.source.clone(),
+                index: item.index,
+                line_number: item.line_number,
+                previous_status: report.status,
+                previous_account_number: report.account_number.clone(),
+                decision: decision.clone(),
+                checksum_valid: match decision {
+                    ReviewDecision::Accept(n) => format.is_valid(n),
+                    ReviewDecision::Reject => false,
+                },
+            });
+            if let ReviewDecision::Accept(account_number) = decision {
+                report.account_number = account_number.clone();
+                report.status = ReportStatus::Reviewed;
+                report.alternatives.clear();
+            }
+        }
+        Ok(audit)
+    }
+}
+
+/// Writes the audit trail as CSV, one row per decision.
+pub fn write_audit_csv<W: Write>(writer: &mut W, audit: &[AuditRecord]) -> io::Result<()> {
+    writeln!(
+        writer,
+        "id,source,index,line_number,previous_status,previous_account_number,decision,checksum_valid"
+    )?;
+    for record in audit {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            record.id,
+            csv_field(record.source.as_deref().unwrap_or("")),
+            record.index,
+            record.line_number,
+            record.previous_status.label(),
+            csv_field(&record.previous_account_number),
+            csv_field(&record.decision.to_string()),
+            record.checksum_valid
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_review_queue {
+    use super::*;
+    use crate::parser::parse_entries;
+    use crate::report::report_entry;
+    use crate::search::SearchOptions;
+
+    const SCAN: &str = "    _  _  _  _  _  _     _ \n|_||_|| || ||_   |  |  ||_ \n  | _||_||_||_|  |  |  | _|\n\n    _  _     _  _  _  _  _ \n  | _| _||_||_ |_   ||_||_|\n  ||_  _|  | _||_|  ||_| _|\n\n    _  _     _  _  _  _  _ \n _| _| _||_||_ |_   ||_||_|\n _||_  _|  | _||_|  ||_| _|\n";
+
+    fn reports(queue: &mut ReviewQueue) -> Vec<EntryReport> {
+        let format = AccountFormat::kata();
+        let search = SearchOptions::default();
+        parse_entries(SCAN.as_bytes())
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let report = report_entry(&entry, Some("scan.txt"), &format, Some(&search));
+                queue.push(&report);
+                report
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_push_only_unresolved() {
+        let mut queue = ReviewQueue::new();
+        let reports = reports(&mut queue);
+        assert_eq!(
+            vec![ReportStatus::Amb, ReportStatus::Ok, ReportStatus::Ill],
+            reports.iter().map(|r| r.status).collect::<Vec<_>>()
+        );
+        assert_eq!(2, queue.len());
+        assert_eq!((1, 0), (queue.items()[0].id, queue.items()[0].index));
+        assert_eq!((2, 2), (queue.items()[1].id, queue.items()[1].index));
+        assert_eq!(3, queue.items()[0].candidates.len());
+        // チェックサムが解いた番号は候補になるが、ILL のまま確認に回る
+        assert_eq!(vec!["123456789"], queue.items()[1].candidates);
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let mut queue = ReviewQueue::new();
+        reports(&mut queue);
+        let mut exported = Vec::new();
+        queue.write_to(&mut exported).unwrap();
+        let exported = String::from_utf8(exported).unwrap();
+        assert!(
+            exported.starts_with("# review 1\nsource: scan.txt\nentry: 0\nline: 1\nstatus: AMB\n")
+        );
+
+        let (imported, decisions) = ReviewQueue::from_reader(exported.as_bytes()).unwrap();
+        assert_eq!(queue, imported);
+        assert!(decisions.is_empty());
+    }
+
+    #[test]
+    fn test_merge_decisions() {
+        let mut queue = ReviewQueue::new();
+        let mut reports = reports(&mut queue);
+        let mut exported = Vec::new();
+        queue.write_to(&mut exported).unwrap();
+        let decided = String::from_utf8(exported)
+            .unwrap()
+            .replacen("decision:", "decision: 490867715", 1)
+            .replacen("decision:\n", "decision: reject\n", 1);
+
+        let (queue, decisions) = ReviewQueue::from_reader(decided.as_bytes()).unwrap();
+        assert_eq!(
+            vec![
+                (1, ReviewDecision::Accept("490867715".to_string())),
+                (2, ReviewDecision::Reject)
+            ],
+            decisions
+        );
+        let audit = queue
+            .merge(&mut reports, &decisions, &AccountFormat::kata())
+            .unwrap();
+        assert_eq!(ReportStatus::Reviewed, reports[0].status);
+        assert_eq!("490867715", reports[0].account_number);
+        assert!(reports[0].alternatives.is_empty());
+        assert_eq!(ReportStatus::Ill, reports[2].status);
+
+        let mut csv = Vec::new();
+        write_audit_csv(&mut csv, &audit).unwrap();
+        assert_eq!(
+            "id,source,index,line_number,previous_status,previous_account_number,decision,checksum_valid\n\
+             1,scan.txt,0,1,AMB,490067715,490867715,true\n\
+             2,scan.txt,2,9,ILL,?23456789,reject,false\n",
+            String::from_utf8(csv).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_decisions() {
+        let item = "# review 1\nraw: 490067715\n";
+        assert!(matches!(
+            ReviewQueue::from_reader(format!("{item}decision: 4900x").as_bytes()),
+            Err(ReviewError::InvalidDecision { line_number: 3, .. })
+        ));
+        assert!(matches!(
+            ReviewQueue::from_reader("decision: reject\n".as_bytes()),
+            Err(ReviewError::InvalidLine { line_number: 1 })
+        ));
+
+        let (queue, _) = ReviewQueue::from_reader(item.as_bytes()).unwrap();
+        let unknown = [(7, ReviewDecision::Reject)];
+        assert!(matches!(
+            queue.merge(&mut [], &unknown, &AccountFormat::kata()),
+            Err(ReviewError::UnknownItem { id: 7 })
+        ));
+        let missing = [(1, ReviewDecision::Reject)];
+        assert!(matches!(
+            queue.merge(&mut [], &missing, &AccountFormat::kata()),
+            Err(ReviewError::MissingEntry { id: 1 })
+        ));
+    }
+
+    fn decided(decision: &str) -> String {
+        let mut queue = ReviewQueue::new();
+        reports(&mut queue);
+        let mut exported = Vec::new();
+        queue.write_to(&mut exported).unwrap();
+        String::from_utf8(exported).unwrap().replacen(
+            "decision:",
+            &format!("decision: {decision}"),
+            1,
+        )
+    }
+
+    #[test]
+    fn test_decision_length() {
+        let (queue, decisions) = ReviewQueue::from_reader(decided("4908").as_bytes()).unwrap();
+        assert_eq!(
+            vec![(1, ReviewDecision::Accept("4908".to_string()))],
+            decisions
+        );
+        let mut reports = reports(&mut ReviewQueue::new());
+        assert!(matches!(
+            queue.merge(&mut reports, &decisions, &AccountFormat::kata()),
+            Err(ReviewError::DecisionLength {
+                id: 1,
+                expected: 9,
+                length: 4
+            })
+        ));
+
+        // raw: の行がなくても、decision: が先にあっても読める
+        let file = decided("490867715")
+            .replace("raw: 490067715\n", "")
+            .replacen("decision: 490867715\n", "", 1)
+            .replacen("entry: 0\n", "decision: 490867715\nentry: 0\n", 1);
+        let (queue, decisions) = ReviewQueue::from_reader(file.as_bytes()).unwrap();
+        assert_eq!(1, decisions.len());
+        queue
+            .merge(&mut reports, &decisions, &AccountFormat::kata())
+            .unwrap();
+        assert_eq!(ReportStatus::Reviewed, reports[0].status);
+    }
+
+    #[test]
+    fn test_entry_mismatch() {
+        let file = decided("490867715");
+        let (queue, decisions) = ReviewQueue::from_reader(file.as_bytes()).unwrap();
+
+        // エディタが行末の空白を消しても同じエントリ
+        let trimmed: String = file
+            .lines()
+            .map(|l| format!("{}\n", l.trim_end()))
+            .collect();
+        let (trimmed, _) = ReviewQueue::from_reader(trimmed.as_bytes()).unwrap();
+        let mut trimmed_reports = reports(&mut ReviewQueue::new());
+        assert!(
+            trimmed
+                .merge(&mut trimmed_reports, &decisions, &AccountFormat::kata())
+                .is_ok()
+        );
+
+        // スキャナファイルが書き換えられた
+        let mut reports = reports(&mut ReviewQueue::new());
+        reports[0].lines[1] = reports[0].lines[1].replacen('|', " ", 1);
+        assert!(matches!(
+            queue.merge(&mut reports, &decisions, &AccountFormat::kata()),
+            Err(ReviewError::EntryMismatch { id: 1 })
+        ));
+        assert_eq!(ReportStatus::Amb, reports[0].status);
+    }
+
+    #[test]
+    fn test_failed_merge_changes_nothing() {
+        let file = decided("490867715").replacen("decision:\n", "decision: 1234\n", 1);
+        let (queue, decisions) = ReviewQueue::from_reader(file.as_bytes()).unwrap();
+        assert_eq!(2, decisions.len());
+        let mut reports = reports(&mut ReviewQueue::new());
+        let before = reports.clone();
+        assert!(matches!(
+            queue.merge(&mut reports, &decisions, &AccountFormat::kata()),
+            Err(ReviewError::DecisionLength { id: 2, .. })
+        ));
+        assert_eq!(before, reports);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::account_number::Glyph;
+use crate::checksum::AccountFormat;
+use crate::glyph_table::GlyphTable;
+use crate::recognizer::cat_number;
+use crate::segments::{Segments, read_glyph};
+
+/// Cost of a segment the digit draws but the scan left blank. Scanners lose ink
+/// more often than they invent it, so this is cheaper than `EXTRA_COST`.
+pub const MISSING_COST: u32 = 2;
+/// Cost of a segment the digit leaves blank, or of a mark outside the segments.
+pub const EXTRA_COST: u32 = 3;
+
+/// How sharply confidence falls off with distance.
+const CONFIDENCE_SHARPNESS: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigitCandidate {
+    pub digit: char,
+    pub distance: u32,
+    /// Share of the likelihood of all candidates, between 0 and 1.
+    pub confidence: f64,
+}
+
+/// The ranked candidates for one glyph, nearest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredDigit {
+    pub glyph: Glyph,
+    pub candidates: Vec<DigitCandidate>,
+}
+
+impl ScoredDigit {
+    /// Returns the nearest candidate, or `None` if the table was empty.
+    pub fn best(&self) -> Option<&DigitCandidate> {
+        self.candidates.first()
+    }
+
+    /// Returns the best digit if the glyph matched it exactly, or `?` otherwise.
+    pub fn exact(&self) -> char {
+        match self.best() {
+            Some(best) if best.distance == 0 => best.digit,
+            _ => '?',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredAccountNumber {
+    pub digits: Vec<ScoredDigit>,
+}
+
+impl ScoredAccountNumber {
+    /// The account number made of the nearest digit of every glyph.
+    pub fn best_guess(&self) -> String {
+        self.digits
+            .iter()
+            .map(|d| d.best().map_or('?', |best| best.digit))
+            .collect()
+    }
+
+    /// The account number as `recognizer::recognize_account_number` reads it.
+    pub fn exact(&self) -> String {
+        self.digits.iter().map(|d| d.exact()).collect()
+    }
+
+    pub fn confidences(&self) -> Vec<f64> {
+        self.digits
+            .iter()
+            .map(|d| d.best().map_or(0.0, |best| best.confidence))
+            .collect()
+    }
+
+    /// The confidence of the least certain digit, used to prioritize manual review.
+    pub fn worst_confidence(&self) -> f64 {
+        self.confidences().into_iter().fold(1.0, f64::min)
+    }
+}
+
+pub fn glyph_distance(scanned: &Glyph, expected: Segments) -> u32 {
+    let reading = read_glyph(scanned);
+    reading.segments.missing_from(expected) * MISSING_COST
+        + (reading.segments.extra_to(expected) + reading.noise) * EXTRA_COST
+}
+
+pub fn score_digit(glyph: Glyph) -> ScoredDigit {
+    score_digit_with(glyph, &GlyphTable::digits())
+}
+
+pub fn score_digit_with(glyph: Glyph, table: &GlyphTable) -> ScoredDigit {
+    let mut candidates: Vec<DigitCandidate> = table
+        .iter()
+        .map(|(digit, expected)| DigitCandidate {
+            digit,
+            distance: glyph_distance(&glyph, expected),
+            confidence: 0.0,
+        })
+        .collect();
+    candidates.sort_by_key(|c| (c.distance, c.digit));
+
+    let weights: Vec<f64> = candidates
+        .iter()
+        .map(|c| (-(c.distance as f64) * CONFIDENCE_SHARPNESS).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+    for (candidate, weight) in candidates.iter_mut().zip(weights) {
+        candidate.confidence = weight / total;
+    }
+    ScoredDigit { glyph, candidates }
+}
+
+pub fn score_account_number(pattern: &str, count: usize) -> ScoredAccountNumber {
+    score_account_number_with(pattern, count, &GlyphTable::digits())
+}
+
+pub fn score_account_number_with(
+    pattern: &str,
+    count: usize,
+    table: &GlyphTable,
+) -> ScoredAccountNumber {
+    ScoredAccountNumber {
+        digits: (0..count)
+            .map(|i| score_digit_with(cat_number(pattern, i), table))
+            .collect(),
+    }
+}
+
+/// Scores the `format.length` glyphs of an entry drawn in `format.layout`
+/// against `format.table`.
+pub fn score_entry(pattern: &str, format: &AccountFormat) -> ScoredAccountNumber {
+    ScoredAccountNumber {
+        digits: (0..format.length)
+            .map(|i| score_digit_with(format.layout.cut(pattern, i), &format.table))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests_scoring {
+    use super::*;
+    use crate::recognizer::{NINE_PATTERN, ONE_PATTERN, SEVEN_PATTERN};
+    use crate::segments::DIGIT_SEGMENTS;
+
+    #[test]
+    fn test_exact_match() {
+        let scored = score_digit(SEVEN_PATTERN);
+        assert_eq!('7', scored.exact());
+        assert_eq!(0, scored.best().unwrap().distance);
+        assert!(scored.best().unwrap().confidence > 0.5);
+        assert_eq!(10, scored.candidates.len());
+        let total: f64 = scored.candidates.iter().map(|c| c.confidence).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_missing_segment_is_cheaper_than_extra() {
+        assert_eq!(
+            MISSING_COST,
+            glyph_distance(&ONE_PATTERN, DIGIT_SEGMENTS[7])
+        );
+        assert_eq!(
+            EXTRA_COST,
+            glyph_distance(&SEVEN_PATTERN, DIGIT_SEGMENTS[1])
+        );
+
+        // 8 から右下の線が欠けたものとも、2 に左上の線が増えたものとも読める
+        let glyph = [[' ', '_', ' '], ['|', '_', '|'], ['|', '_', ' ']];
+        let scored = score_digit(glyph);
+        assert_eq!('?', scored.exact());
+        assert_eq!('8', scored.candidates[0].digit);
+        assert_eq!(MISSING_COST, scored.candidates[0].distance);
+        assert_eq!('2', scored.candidates[1].digit);
+        assert_eq!(EXTRA_COST, scored.candidates[1].distance);
+    }
+
+    #[test]
+    fn test_ranking() {
+        // 0 の下の線が欠けたもの
+        let glyph = [[' ', '_', ' '], ['|', ' ', '|'], ['|', ' ', '|']];
+        let scored = score_digit(glyph);
+        assert_eq!('0', scored.best().unwrap().digit);
+        let distances: Vec<u32> = scored.candidates.iter().map(|c| c.distance).collect();
+        let mut sorted = distances.clone();
+        sorted.sort();
+        assert_eq!(sorted, distances);
+        assert!(scored.candidates[0].confidence > scored.candidates[1].confidence);
+    }
+
+    #[test]
+    fn test_empty_table() {
+        let scored = score_digit_with(SEVEN_PATTERN, &GlyphTable::new());
+        assert_eq!(None, scored.best());
+        assert_eq!('?', scored.exact());
+        let number = ScoredAccountNumber {
+            digits: vec![scored],
+        };
+        assert_eq!("?", number.best_guess());
+        assert_eq!(0.0, number.worst_confidence());
+    }
+
+    #[test]
+    fn test_score_account_number() {
+        let pattern = "    _  _  _  _  _  _     _ \n|_||_|| || ||_   |  |  | _ \n  | _||_||_||_|  |  |  | _|\n                           ";
+        let scored = score_account_number(pattern, 9);
+        assert_eq!("49006771?", scored.exact());
+        assert_eq!(9, scored.confidences().len());
+        assert_eq!(
+            scored.digits[8].best().unwrap().confidence,
+            scored.worst_confidence()
+        );
+        assert!(scored.worst_confidence() < scored.digits[0].best().unwrap().confidence);
+        assert_eq!(NINE_PATTERN, scored.digits[1].glyph);
+    }
+}
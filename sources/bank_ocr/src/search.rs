@@ -0,0 +1,326 @@
+use crate::checksum::AccountFormat;
+use crate::scoring::glyph_distance;
+use crate::segments::read_glyph;
+
+/// The most segment edits a search makes. The number of candidates grows
+/// quickly with every edit allowed, so larger `max_edits` are clamped to it.
+pub const MAX_EDITS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// The maximum number of segments added or removed across the whole entry,
+    /// at most `MAX_EDITS`. A mark outside the segments counts as one edit.
+    pub max_edits: u32,
+    /// Stop searching with more edits once this many candidates are found;
+    /// those with the fewest edits, then the cheapest, are kept.
+    pub max_candidates: usize,
+}
+
+impl Default for SearchOptions {
+    /// One pipe or underscore, as in User Story 4.
+    fn default() -> Self {
+        Self {
+            max_edits: 1,
+            max_candidates: 100,
+        }
+    }
+}
+
+/// A valid-checksum account number reachable from the scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub account_number: String,
+    /// Number of segment edits.
+    pub edits: u32,
+    /// Weighted cost of the changes, see `scoring::glyph_distance`.
+    pub cost: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DigitOption {
+    digit: u8,
+    edits: u32,
+    cost: u32,
+}
+
+struct Search<'a> {
+    options: &'a [Vec<DigitOption>],
+    /// `min_edits[i]` is the fewest edits positions `i..` need, for pruning.
+    min_edits: Vec<u32>,
+    format: &'a AccountFormat,
+    digits: Vec<u8>,
+    found: Vec<Candidate>,
+}
+
+impl Search<'_> {
+    /// Collects the candidates with exactly `budget` edits.
+    fn visit(&mut self, position: usize, edits: u32, cost: u32, budget: u32) {
+        if edits + self.min_edits[position] > budget {
+            return;
+        }
+        if position == self.options.len() {
+            if edits == budget && self.format.checksum.is_valid(&self.digits) {
+                self.found.push(Candidate {
+                    account_number: self.digits.iter().map(|d| (b'0' + d) as char).collect(),
+                    edits,
+                    cost,
+                });
+            }
+            return;
+        }
+        for i in 0..self.options[position].len() {
+            let option = self.options[position][i];
+            if edits + option.edits > budget {
+                continue;
+            }
+            self.digits[position] = option.digit;
+            self.visit(
+                position + 1,
+                edits + option.edits,
+                cost + option.cost,
+                budget,
+            );
+        }
+    }
+}
+
+/// Finds the valid-checksum account numbers within `options.max_edits` segment edits
+/// of the scanned entry, cheapest first. Only the digits of `format.table` are
+/// tried, since the checksum is computed over digits. Numbers are searched one
+/// more edit at a time until `options.max_candidates` are found.
+pub fn search_corrections(
+    pattern: &str,
+    format: &AccountFormat,
+    options: &SearchOptions,
+) -> Vec<Candidate> {
+    let max_edits = options.max_edits.min(MAX_EDITS);
+    let per_position: Vec<Vec<DigitOption>> = (0..format.length)
+        .map(|i| {
+            let glyph = format.layout.cut(pattern, i);
+            let reading = read_glyph(&glyph);
+            let mut position_options: Vec<DigitOption> = format
+                .table
+                .iter()
+                .filter_map(|(c, expected)| {
+                    let digit = c.to_digit(10)?;
+                    let edits = reading.edits_to(expected);
+                    (edits <= max_edits).then(|| DigitOption {
+                        digit: digit as u8,
+                        edits,
+                        cost: glyph_distance(&glyph, expected),
+                    })
+                })
+                .collect();
+            position_options.sort_by_key(|o| (o.cost, o.edits));
+            position_options
+        })
+        .collect();
+
+    let mut min_edits = vec![0; format.length + 1];
+    for i in (0..format.length).rev() {
+        let Some(fewest) = per_position[i].iter().map(|o| o.edits).min() else {
+            // どの数字からも max_edits 以上離れている桁がある
+            return Vec::new();
+        };
+        min_edits[i] = min_edits[i + 1] + fewest;
+    }
+
+    let mut search = Search {
+        options: &per_position,
+        min_edits,
+        format,
+        digits: vec![0; format.length],
+        found: Vec::new(),
+    };
+    // 編集数の少ない順に探し、候補が揃ったらそれより多い編集は試さない
+    for budget in 0..=max_edits {
+        if search.found.len() >= options.max_candidates {
+            break;
+        }
+        search.visit(0, 0, 0, budget);
+    }
+
+    let mut found = search.found;
+    found.sort_by(|a, b| {
+        (a.cost, a.edits, &a.account_number).cmp(&(b.cost, b.edits, &b.account_number))
+    });
+    found.truncate(options.max_candidates);
+    found
+}
+
+#[cfg(test)]
+mod tests_search_corrections {
+    use super::*;
+    use crate::noise::{Noise, NoiseGenerator};
+    use crate::parser::parse_entries;
+    use crate::renderer::render_account_number;
+
+    fn numbers(candidates: &[Candidate]) -> Vec<&str> {
+        candidates
+            .iter()
+            .map(|c| c.account_number.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_search_one_edit_matches_user_story_4() {
+        let pattern = render_account_number("490067715").unwrap();
+        let candidates =
+            search_corrections(&pattern, &AccountFormat::kata(), &SearchOptions::default());
+        let mut found = numbers(&candidates);
+        found.sort();
+        assert_eq!(vec!["490067115", "490067719", "490867715"], found);
+        assert!(candidates.iter().all(|c| c.edits == 1));
+    }
+
+    #[test]
+    fn test_search_valid_number_first() {
+        let pattern = render_account_number("457508000").unwrap();
+        let options = SearchOptions {
+            max_edits: 2,
+            ..SearchOptions::default()
+        };
+        let candidates = search_corrections(&pattern, &AccountFormat::kata(), &options);
+        assert_eq!("457508000", candidates[0].account_number);
+        assert_eq!(0, candidates[0].edits);
+        assert!(
+            candidates
+                .windows(2)
+                .all(|w| (w[0].cost, w[0].edits) <= (w[1].cost, w[1].edits))
+        );
+    }
+
+    #[test]
+    fn test_search_two_lost_segments() {
+        // 123456789 の 8 から縦線が2本欠けたもの
+        let pattern = render_account_number("123456789")
+            .unwrap()
+            .replacen("||_||_|", "| _ |_|", 1);
+        assert!(
+            search_corrections(&pattern, &AccountFormat::kata(), &SearchOptions::default())
+                .is_empty()
+        );
+
+        let options = SearchOptions {
+            max_edits: 2,
+            ..SearchOptions::default()
+        };
+        let candidates = search_corrections(&pattern, &AccountFormat::kata(), &options);
+        assert!(numbers(&candidates).contains(&"123456789"));
+    }
+
+    #[test]
+    fn test_search_tries_digits_of_format_table() {
+        use crate::glyph_table::GlyphTable;
+
+        let pattern = render_account_number("490067715").unwrap();
+        let format = AccountFormat::kata().with_table(GlyphTable::hex());
+        let mut found = search_corrections(&pattern, &format, &SearchOptions::default());
+        found.sort_by(|a, b| a.account_number.cmp(&b.account_number));
+        assert_eq!(vec!["490067115", "490067719", "490867715"], numbers(&found));
+    }
+
+    #[test]
+    fn test_search_clamps_max_edits() {
+        let pattern = render_account_number("888888888").unwrap();
+        let options = |max_edits| SearchOptions {
+            max_edits,
+            max_candidates: usize::MAX,
+        };
+        let format = AccountFormat::kata();
+        assert_eq!(
+            search_corrections(&pattern, &format, &options(MAX_EDITS)),
+            search_corrections(&pattern, &format, &options(u32::MAX))
+        );
+    }
+
+    #[test]
+    fn test_search_max_candidates() {
+        let pattern = render_account_number("888888888").unwrap();
+        let options = SearchOptions {
+            max_edits: 3,
+            max_candidates: 5,
+        };
+        let candidates = search_corrections(&pattern, &AccountFormat::kata(), &options);
+        assert_eq!(5, candidates.len());
+        assert!(candidates.iter().all(|c| c.edits <= 2));
+
+        // 1本で届く3件が揃えば、2本の編集は探さない
+        let options = SearchOptions {
+            max_edits: 3,
+            max_candidates: 3,
+        };
+        let candidates = search_corrections(&pattern, &AccountFormat::kata(), &options);
+        assert_eq!(
+            vec!["888886888", "888888880", "888888988"],
+            numbers(&candidates)
+        );
+        assert!(candidates.iter().all(|c| c.edits == 1));
+    }
+
+    #[test]
+    fn test_search_500_entries() {
+        let fixture = NoiseGenerator::new(35)
+            .with_noise(Noise::MultiSegment(2), 1)
+            .generate(500);
+        let options = SearchOptions {
+            max_edits: 3,
+            max_candidates: usize::MAX,
+        };
+        let format = AccountFormat::kata();
+        for (entry, answer) in
+            parse_entries(fixture.scanner_file.as_bytes()).zip(&fixture.answer_key)
+        {
+            let candidates = search_corrections(&entry.unwrap().text(), &format, &options);
+            assert!(numbers(&candidates).contains(&answer.account_number.as_str()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_search_properties {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::segments::{DIGIT_SEGMENTS, Segments};
+    use crate::validator::{MissingDigit, solve_missing_digit};
+
+    /// Kata account numbers: eight digits and the check digit that fits them.
+    fn valid_account_number() -> impl Strategy<Value = String> {
+        "[0-9]{8}".prop_filter_map("no check digit fits", |prefix| {
+            match solve_missing_digit(&format!("{prefix}?")) {
+                Some(MissingDigit::Digit(d)) => Some(format!("{prefix}{d}")),
+                _ => None,
+            }
+        })
+    }
+
+    fn draw(digits: &[Segments]) -> String {
+        let mut lines = vec![String::new(); 3];
+        for segments in digits {
+            for (line, row) in lines.iter_mut().zip(segments.to_glyph()) {
+                line.extend(row);
+            }
+        }
+        lines.join("\n")
+    }
+
+    proptest! {
+        #[test]
+        fn one_segment_damage_is_recoverable(
+            account_number in valid_account_number(),
+            position in 0..9usize,
+            segment in 0..7u8,
+        ) {
+            let mut digits: Vec<Segments> = account_number
+                .chars()
+                .map(|c| DIGIT_SEGMENTS[c.to_digit(10).unwrap() as usize])
+                .collect();
+            digits[position] = digits[position].toggle(Segments::from_bits(1 << segment));
+
+            let candidates =
+                search_corrections(&draw(&digits), &AccountFormat::kata(), &SearchOptions::default());
+            prop_assert!(candidates.iter().any(|c| c.account_number == account_number));
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt;
+
+use crate::account_number::Glyph;
+
+/// The seven segments of a digit as a bitmask, `a` to `g`:
+///
+/// ```text
+///  _        a
+/// |_|  =  f g b
+/// |_|     e d c
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord)]
+pub struct Segments(u8);
+
+pub const A: Segments = Segments(1 << 0);
+pub const B: Segments = Segments(1 << 1);
+pub const C: Segments = Segments(1 << 2);
+pub const D: Segments = Segments(1 << 3);
+pub const E: Segments = Segments(1 << 4);
+pub const F: Segments = Segments(1 << 5);
+pub const G: Segments = Segments(1 << 6);
+
+/// Where each segment is drawn in a 3x3 glyph, and with which character.
+pub const SEGMENT_CELLS: [(Segments, usize, usize, char); 7] = [
+    (A, 0, 1, '_'),
+    (F, 1, 0, '|'),
+    (G, 1, 1, '_'),
+    (B, 1, 2, '|'),
+    (E, 2, 0, '|'),
+    (D, 2, 1, '_'),
+    (C, 2, 2, '|'),
+];
+
+/// The segments of `0` to `9`.
+pub const DIGIT_SEGMENTS: [Segments; 10] = [
+    Segments(A.0 | B.0 | C.0 | D.0 | E.0 | F.0),
+    Segments(B.0 | C.0),
+    Segments(A.0 | B.0 | D.0 | E.0 | G.0),
+    Segments(A.0 | B.0 | C.0 | D.0 | G.0),
+    Segments(B.0 | C.0 | F.0 | G.0),
+    Segments(A.0 | C.0 | D.0 | F.0 | G.0),
+    Segments(A.0 | C.0 | D.0 | E.0 | F.0 | G.0),
+    Segments(A.0 | B.0 | C.0),
+    Segments(A.0 | B.0 | C.0 | D.0 | E.0 | F.0 | G.0),
+    Segments(A.0 | B.0 | C.0 | D.0 | F.0 | G.0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentError {
+    /// `found` is not the character of a segment at this cell.
+    InvalidCell {
+        row: usize,
+        column: usize,
+        found: char,
+    },
+}
+
+impl fmt::Display for SegmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SegmentError::InvalidCell { row, column, found } => {
+                write!(
+                    f,
+                    "{found:?} is not a segment at row {row}, column {column}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SegmentError {}
+
+/// What a scanned glyph reads as: the segments that are drawn, plus the number
+/// of cells holding something that is not a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reading {
+    pub segments: Segments,
+    pub noise: u32,
+}
+
+impl Segments {
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Segments(bits & 0x7f)
+    }
+
+    pub const fn contains(self, other: Segments) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Segments) -> Self {
+        Segments(self.0 | other.0)
+    }
+
+    /// Adds the segment if it is off, or removes it if it is on.
+    pub const fn toggle(self, other: Segments) -> Self {
+        Segments(self.0 ^ other.0)
+    }
+
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// Segments of `expected` that are off in `self`.
+    pub const fn missing_from(self, expected: Segments) -> u32 {
+        (expected.0 & !self.0).count_ones()
+    }
+
+    /// Segments of `self` that are off in `expected`.
+    pub const fn extra_to(self, expected: Segments) -> u32 {
+        (self.0 & !expected.0).count_ones()
+    }
+
+    /// The number of segments to add or remove to turn `self` into `other`.
+    pub const fn distance(self, other: Segments) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+
+    /// Reads a glyph, rejecting characters outside the valid segment positions.
+    pub fn from_glyph(glyph: &Glyph) -> Result<Self, SegmentError> {
+        let mut segments = Segments::default();
+        for (row, cells) in glyph.iter().enumerate() {
+            for (column, &found) in cells.iter().enumerate() {
+                if found == ' ' {
+                    continue;
+                }
+                match segment_at(row, column) {
+                    Some((segment, c)) if c == found => segments = segments.union(segment),
+                    _ => return Err(SegmentError::InvalidCell { row, column, found }),
+                }
+            }
+        }
+        Ok(segments)
+    }
+
+    pub fn to_glyph(self) -> Glyph {
+        let mut glyph = [[' '; 3]; 3];
+        for (segment, row, column, c) in SEGMENT_CELLS {
+            if self.contains(segment) {
+                glyph[row][column] = c;
+            }
+        }
+        glyph
+    }
+
+    /// Returns the digit drawn by these segments, if any.
+    pub fn digit(self) -> Option<char> {
+        DIGIT_SEGMENTS
+            .iter()
+            .position(|s| *s == self)
+            .map(|d| (b'0' + d as u8) as char)
+    }
+}
+
+fn segment_at(row: usize, column: usize) -> Option<(Segments, char)> {
+    SEGMENT_CELLS
+        .iter()
+        .find(|(_, r, c, _)| *r == row && *c == column)
+        .map(|(segment, _, _, c)| (*segment, *c))
+}
+
+/// Reads a glyph the tolerant way: invalid cells do not draw a segment and are
+/// counted as noise instead.
+pub fn read_glyph(glyph: &Glyph) -> Reading {
+    let mut reading = Reading {
+        segments: Segments::default(),
+        noise: 0,
+    };
+    for (row, cells) in glyph.iter().enumerate() {
+        for (column, &found) in cells.iter().enumerate() {
+            if found == ' ' {
+                continue;
+            }
+            match segment_at(row, column) {
+                Some((segment, c)) if c == found => {
+                    reading.segments = reading.segments.union(segment)
+                }
+                _ => reading.noise += 1,
+            }
+        }
+    }
+    reading
+}
+
+impl Reading {
+    /// Segment edits needed to turn the scan into `expected`; every noise cell is one edit.
+    pub fn edits_to(&self, expected: Segments) -> u32 {
+        self.segments.distance(expected) + self.noise
+    }
+}
+
+#[cfg(test)]
+mod tests_segments {
+    use super::*;
+    use crate::recognizer::get_digit_pattern;
+
+    #[test]
+    fn test_digit_segments_match_patterns() {
+        for (d, segments) in DIGIT_SEGMENTS.iter().enumerate() {
+            let c = (b'0' + d as u8) as char;
+            assert_eq!(
+                *segments,
+                Segments::from_glyph(&get_digit_pattern(c)).unwrap()
+            );
+            assert_eq!(get_digit_pattern(c), segments.to_glyph());
+            assert_eq!(Some(c), segments.digit());
+        }
+    }
+
+    #[test]
+    fn test_reject_invalid_cells() {
+        let pipe_on_top = [[' ', '|', ' '], [' ', ' ', '|'], [' ', ' ', '|']];
+        assert_eq!(
+            Err(SegmentError::InvalidCell {
+                row: 0,
+                column: 1,
+                found: '|'
+            }),
+            Segments::from_glyph(&pipe_on_top)
+        );
+        let corner = [['_', '_', ' '], [' ', ' ', '|'], [' ', ' ', '|']];
+        assert!(Segments::from_glyph(&corner).is_err());
+
+        let reading = read_glyph(&corner);
+        assert_eq!(DIGIT_SEGMENTS[7], reading.segments);
+        assert_eq!(1, reading.noise);
+        assert_eq!(1, reading.edits_to(DIGIT_SEGMENTS[7]));
+    }
+
+    #[test]
+    fn test_segment_distance() {
+        let eight = DIGIT_SEGMENTS[8];
+        let zero = DIGIT_SEGMENTS[0];
+        assert_eq!(1, eight.distance(zero));
+        assert_eq!(1, zero.missing_from(eight));
+        assert_eq!(0, zero.extra_to(eight));
+        assert_eq!(eight, zero.toggle(G));
+        assert_eq!(None, eight.toggle(C).digit());
+        assert_eq!(7, eight.count());
+    }
+}
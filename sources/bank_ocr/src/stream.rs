@@ -0,0 +1,169 @@
+use std::io::BufRead;
+
+use crate::checksum::AccountFormat;
+use crate::parser::{Entries, Entry, ParseError, parse_entries_normalized};
+use crate::registry::AccountRegistry;
+use crate::report::{EntryReport, report_entry_with_registry};
+use crate::search::SearchOptions;
+
+/// Streams the reports of the entries of a scanner file, one entry at a time,
+/// so memory use does not grow with the length of the file. The entries are
+/// usually `Entries`, but may come from anywhere, e.g. `image::Scan::to_entries`.
+///
+/// A broken entry is yielded as an error, and the entries after it are still
+/// reported, as `Entries` resumes after the next blank line.
+pub struct Reports<'a, I> {
+    entries: I,
+    format: &'a AccountFormat,
+    search: Option<SearchOptions>,
+    registry: Option<&'a AccountRegistry>,
+    source: Option<&'a str>,
+}
+
+/// Normalizes, recognizes, validates and corrects with the default search
+/// every entry read from `reader`.
+pub fn process_entries<R: BufRead>(reader: R, format: &AccountFormat) -> Reports<'_, Entries<R>> {
+    let entries = parse_entries_normalized(reader, format.width())
+        .with_layout(format.layout)
+        .with_table(format.table.clone());
+    Reports::new(entries, format).with_search(Some(SearchOptions::default()))
+}
+
+impl<'a, I> Reports<'a, I>
+where
+    I: Iterator<Item = Result<Entry, ParseError>>,
+{
+    /// Reports `entries` without correcting them.
+    pub fn new(entries: I, format: &'a AccountFormat) -> Self {
+        Self {
+            entries,
+            format,
+            search: None,
+            registry: None,
+            source: None,
+        }
+    }
+
+    /// Corrects ERR and ILL numbers when `search` is given.
+    pub fn with_search(mut self, search: Option<SearchOptions>) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// See `report_entry_with_registry`.
+    pub fn with_registry(mut self, registry: Option<&'a AccountRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// The file name given as the source of every report.
+    pub fn with_source(mut self, source: Option<&'a str>) -> Self {
+        self.source = source;
+        self
+    }
+}
+
+impl<I> Iterator for Reports<'_, I>
+where
+    I: Iterator<Item = Result<Entry, ParseError>>,
+{
+    type Item = Result<EntryReport, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some(entry.map(|entry| {
+            report_entry_with_registry(
+                &entry,
+                self.source,
+                self.format,
+                self.search.as_ref(),
+                self.registry,
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests_reports {
+    use std::io::{BufReader, Read};
+
+    use super::*;
+    use crate::parser::parse_entries;
+    use crate::renderer::render_account_number;
+    use crate::report::ReportStatus;
+
+    /// Repeats an entry forever, without ever holding more than one copy.
+    struct Endless {
+        entry: Vec<u8>,
+        at: usize,
+    }
+
+    impl Read for Endless {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.entry.len() - self.at);
+            buf[..n].copy_from_slice(&self.entry[self.at..self.at + n]);
+            self.at = (self.at + n) % self.entry.len();
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_endless_reader() {
+        let entry = render_account_number("664371495").unwrap() + "\n";
+        let reader = BufReader::new(Endless {
+            entry: entry.into_bytes(),
+            at: 0,
+        });
+        let format = AccountFormat::kata();
+        let reports: Vec<EntryReport> = process_entries(reader, &format)
+            .with_source(Some("scanner"))
+            .take(1000)
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(1000, reports.len());
+        assert_eq!(999, reports[999].index);
+        assert_eq!(3997, reports[999].line_number);
+        assert_eq!("664371485", reports[999].account_number);
+        assert_eq!(ReportStatus::Corrected, reports[999].status);
+        assert_eq!(Some("scanner".to_string()), reports[0].source);
+    }
+
+    #[test]
+    fn test_error_per_item() {
+        let format = AccountFormat::kata();
+        let valid = render_account_number("123456789").unwrap();
+        let file = format!("{valid}\n{valid}\nshort\n\n");
+        let mut reports = Reports::new(parse_entries(file.as_bytes()), &format);
+
+        assert_eq!(ReportStatus::Ok, reports.next().unwrap().unwrap().status);
+        assert_eq!(ReportStatus::Ok, reports.next().unwrap().unwrap().status);
+        assert!(matches!(
+            reports.next(),
+            Some(Err(ParseError::TruncatedEntry { .. }))
+        ));
+        assert!(reports.next().is_none());
+    }
+
+    #[test]
+    fn test_resume_after_error() {
+        let format = AccountFormat::kata();
+        let valid = render_account_number("123456789").unwrap();
+        let corrupted = render_account_number("664371495")
+            .unwrap()
+            .replacen(' ', "", 3);
+        let file = format!("{valid}\n{corrupted}\n{valid}\n{corrupted}\n");
+        let reports: Vec<Result<EntryReport, ParseError>> =
+            Reports::new(parse_entries(file.as_bytes()), &format).collect();
+
+        assert_eq!(4, reports.len());
+        assert!(matches!(
+            reports[1],
+            Err(ParseError::InvalidLineLength { line_number: 5, .. })
+        ));
+        let resumed = reports[2].as_ref().unwrap();
+        assert_eq!((2, 9), (resumed.index, resumed.line_number));
+        assert_eq!(ReportStatus::Ok, resumed.status);
+        assert!(reports[3].is_err());
+    }
+}
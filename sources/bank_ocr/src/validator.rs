@@ -1,10 +1,109 @@
+use crate::checksum::{Checksum, Mod11};
+
 pub fn is_valid_account_number(account_number: &str) -> bool {
-    let mut check_sum = 0;
-    let base = '0' as i32;
-    for (i, v) in account_number.bytes().rev().enumerate() {
-        check_sum += (v as i32 - base) * (i as i32 + 1);
+    if !account_number.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let digits: Vec<u8> = account_number.bytes().map(|b| b - b'0').collect();
+    Mod11.is_valid(&digits)
+}
+
+/// What the mod 11 checksum says about the one illegible digit of a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingDigit {
+    /// Only this digit gives a valid checksum.
+    Digit(char),
+    /// No digit gives a valid checksum.
+    Impossible,
+    /// The weight of the position is a multiple of 11, so every digit gives
+    /// the same checksum; it is valid for all of them or for none.
+    Underdetermined { valid: bool },
+}
+
+/// Solves `(d1 + 2*d2 + ... + 9*d9) mod 11 = 0` for the single `?` of
+/// `account_number`. Returns `None` unless the rest are digits.
+pub fn solve_missing_digit(account_number: &str) -> Option<MissingDigit> {
+    let length = account_number.len();
+    let mut missing = None;
+    let mut sum = 0;
+    for (i, b) in account_number.bytes().enumerate() {
+        let weight = (length - i) % 11;
+        match b {
+            b'0'..=b'9' => sum = (sum + weight * (b - b'0') as usize) % 11,
+            b'?' if missing.is_none() => missing = Some(weight),
+            _ => return None,
+        }
+    }
+
+    let weight = missing?;
+    if weight == 0 {
+        return Some(MissingDigit::Underdetermined { valid: sum == 0 });
+    }
+    // weight * d = -sum (mod 11)。11 は素数なので weight の逆元 weight^9 を掛ける
+    let inverse = (0..9).fold(1, |acc, _| acc * weight % 11);
+    let digit = (11 - sum) % 11 * inverse % 11;
+    Some(match char::from_digit(digit as u32, 10) {
+        Some(d) => MissingDigit::Digit(d),
+        None => MissingDigit::Impossible,
+    })
+}
+
+#[cfg(test)]
+mod tests_solve_missing_digit {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        assert_eq!(
+            Some(MissingDigit::Digit('1')),
+            solve_missing_digit("?23456789")
+        );
+        assert_eq!(
+            Some(MissingDigit::Digit('4')),
+            solve_missing_digit("123?56789")
+        );
+        for account_number in ["457508000", "345882865", "490867715"] {
+            for i in 0..9 {
+                let mut illegible = account_number.to_string();
+                let digit = illegible.remove(i);
+                illegible.insert(i, '?');
+                assert_eq!(
+                    Some(MissingDigit::Digit(digit)),
+                    solve_missing_digit(&illegible)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_impossible() {
+        // 9 桁目の重みは 1 で、残りの和は 5 * 9 = 1 (mod 11) なので 10 が必要になる
+        assert_eq!(
+            Some(MissingDigit::Impossible),
+            solve_missing_digit("50000000?")
+        );
+        assert!(!(0..10).any(|d| is_valid_account_number(&format!("50000000{d}"))));
+    }
+
+    #[test]
+    fn test_underdetermined() {
+        // 11 桁の先頭の重みは 11 で、どの数字でもチェックサムは変わらない
+        assert_eq!(
+            Some(MissingDigit::Underdetermined { valid: true }),
+            solve_missing_digit("?0000000000")
+        );
+        assert_eq!(
+            Some(MissingDigit::Underdetermined { valid: false }),
+            solve_missing_digit("?0000000001")
+        );
+    }
+
+    #[test]
+    fn test_not_one_missing_digit() {
+        assert_eq!(None, solve_missing_digit("123456789"));
+        assert_eq!(None, solve_missing_digit("86110??36"));
+        assert_eq!(None, solve_missing_digit("12345678x"));
     }
-    check_sum % 11 == 0
 }
 
 #[cfg(test)]
@@ -21,4 +120,50 @@ mod tests_check_sum {
     fn test_check_sum_false() {
         assert!(!is_valid_account_number("123456780"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_check_sum_illegible() {
+        // '?' を数字として計算するとチェックサムが通ってしまう
+        assert!(!is_valid_account_number("123?56789"));
+        assert!(!is_valid_account_number("86110??36"));
+    }
+}
+
+#[cfg(test)]
+mod tests_validator_properties {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn arbitrary_text_does_not_panic(text in "(?s).{0,30}") {
+            is_valid_account_number(&text);
+            solve_missing_digit(&text);
+        }
+
+        #[test]
+        fn non_digits_are_never_valid(
+            prefix in "[0-9]{0,8}",
+            other in "[^0-9]",
+            suffix in "[0-9]{0,8}",
+        ) {
+            let account_number = format!("{prefix}{other}{suffix}");
+            prop_assert!(!is_valid_account_number(&account_number));
+        }
+
+        #[test]
+        fn solved_digit_is_the_only_valid_one(digits in "[0-9]{8}", position in 0..9usize) {
+            let mut illegible = digits.clone();
+            illegible.insert(position, '?');
+            let valid: Vec<char> = ('0'..='9')
+                .filter(|&d| is_valid_account_number(&illegible.replace('?', &d.to_string())))
+                .collect();
+            match solve_missing_digit(&illegible) {
+                Some(MissingDigit::Digit(d)) => prop_assert_eq!(vec![d], valid),
+                Some(MissingDigit::Impossible) => prop_assert!(valid.is_empty()),
+                other => prop_assert!(false, "unexpected {:?}", other),
+            }
+        }
+    }
+}
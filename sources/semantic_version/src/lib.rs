@@ -1,13 +1,31 @@
+use std::cmp::Ordering;
+use std::fmt;
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq)]
 pub struct ParseVersionError;
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+/// プレリリースバージョンの識別子
+///
+/// 数値の識別子は数値として比較し、英数字の識別子より常に小さい。
+/// 英数字の識別子はASCIIの順に比較する。
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum Identifier {
+    Numeric(u64),
+    AlphaNumeric(String),
+}
+
+/// 比較はSemVerの優先順位に従い、ビルドメタデータは無視する。
+/// そのため`1.0.0+build.1`と`1.0.0+build.2`は等しい。
+#[derive(Debug, Clone)]
 pub struct Version {
     pub major: u64,
     pub minor: u64,
     pub patch: u64,
+    /// `-`に続くプレリリース識別子。リリースバージョンでは空
+    pub pre: Vec<Identifier>,
+    /// `+`に続くビルドメタデータ
+    pub build: Vec<String>,
 }
 
 impl Version {
@@ -16,11 +34,91 @@ impl Version {
             major,
             minor,
             patch,
+            pre: Vec::new(),
+            build: Vec::new(),
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("{}.{}.{}", self.major, self.minor, self.patch)
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // 1. major, minor, patch の順に数値で比較する
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            // 2. プレリリースはリリースより小さい
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                // 3. 識別子を先頭から比較し、すべて等しければ数の多いほうが大きい
+                (false, false) => self.pre.cmp(&other.pre),
+            })
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Identifier::Numeric(n) => write!(f, "{n}"),
+            Identifier::AlphaNumeric(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            let pre: Vec<String> = self.pre.iter().map(Identifier::to_string).collect();
+            write!(f, "-{}", pre.join("."))?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+/// 識別子に使える文字は英数字とハイフンだけで、空であってはならない
+fn is_valid_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// 数値は先頭に0を付けてはならない（`0`そのものは良い）
+fn parse_numeric(s: &str) -> Result<u64, ParseVersionError> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) || (s.len() > 1 && s.starts_with('0'))
+    {
+        return Err(ParseVersionError);
+    }
+    s.parse::<u64>().map_err(|_| ParseVersionError)
+}
+
+impl FromStr for Identifier {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !is_valid_identifier(s) {
+            return Err(ParseVersionError);
+        }
+        if s.chars().all(|c| c.is_ascii_digit()) {
+            parse_numeric(s).map(Identifier::Numeric)
+        } else {
+            Ok(Identifier::AlphaNumeric(s.to_string()))
+        }
     }
 }
 
@@ -29,25 +127,58 @@ impl FromStr for Version {
     type Err = ParseVersionError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // 1. 文字列を"."で分割する
-        let parts: Vec<&str> = s.split('.').collect();
+        // 1. "+"以降はビルドメタデータ、"-"以降はプレリリース
+        let (s, build) = match s.split_once('+') {
+            Some((s, build)) => (s, Some(build)),
+            None => (s, None),
+        };
+        let (core, pre) = match s.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (s, None),
+        };
 
-        // 2. 要素が3つでなければエラー
+        // 2. 残りを"."で分割し、要素が3つでなければエラー
+        let parts: Vec<&str> = core.split('.').collect();
         if parts.len() != 3 {
             return Err(ParseVersionError);
         }
 
         // 3. 各要素をu64にパースする
         //    どれか一つでもパースに失敗したらエラー
-        let major = parts[0].parse::<u64>().map_err(|_| ParseVersionError)?;
-        let minor = parts[1].parse::<u64>().map_err(|_| ParseVersionError)?;
-        let patch = parts[2].parse::<u64>().map_err(|_| ParseVersionError)?;
+        let major = parse_numeric(parts[0])?;
+        let minor = parse_numeric(parts[1])?;
+        let patch = parse_numeric(parts[2])?;
+
+        // 4. 識別子はどれも空であってはならない
+        let pre = match pre {
+            Some(pre) => pre
+                .split('.')
+                .map(Identifier::from_str)
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        let build = match build {
+            Some(build) => build
+                .split('.')
+                .map(|identifier| {
+                    // ビルドメタデータは先頭の0が許される
+                    if is_valid_identifier(identifier) {
+                        Ok(identifier.to_string())
+                    } else {
+                        Err(ParseVersionError)
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
 
-        // 4. パースした値でVersion構造体を作成して返す
+        // 5. パースした値でVersion構造体を作成して返す
         Ok(Version {
             major,
             minor,
             patch,
+            pre,
+            build,
         })
     }
 }
@@ -66,14 +197,7 @@ mod tests {
     fn test_parse_valid_version() {
         //      .parse()メソッドを呼び出すと、内部でFromStr::from_str      が呼ばれる
         let version: Version = "1.2.3".parse().unwrap();
-        assert_eq!(
-            version,
-            Version {
-                major: 1,
-                minor: 2,
-                patch: 3
-            }
-        );
+        assert_eq!(version, Version::new(1, 2, 3));
     }
 
     #[test]
@@ -81,5 +205,177 @@ mod tests {
         //      不正な文字列をパースするとErrが返ることを確認
         let result: Result<Version, _> = "1.2.a".parse();
         assert_eq!(result, Err(ParseVersionError));
+        for s in [
+            "1.2", "1", "a.b.c", "1.2.3.4", "", "01.2.3", "1.02.3", "1.2.+3",
+        ] {
+            assert_eq!(s.parse::<Version>(), Err(ParseVersionError), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_parse_pre_release() {
+        let version: Version = "1.0.0-alpha.1".parse().unwrap();
+        assert_eq!(
+            version.pre,
+            vec![
+                Identifier::AlphaNumeric("alpha".to_string()),
+                Identifier::Numeric(1)
+            ]
+        );
+        let version: Version = "1.0.0-0.3.7".parse().unwrap();
+        assert_eq!(
+            version.pre,
+            vec![
+                Identifier::Numeric(0),
+                Identifier::Numeric(3),
+                Identifier::Numeric(7)
+            ]
+        );
+        // ハイフンも識別子の文字になる
+        let version: Version = "1.0.0-x-y-z.--".parse().unwrap();
+        assert_eq!(
+            version.pre,
+            vec![
+                Identifier::AlphaNumeric("x-y-z".to_string()),
+                Identifier::AlphaNumeric("--".to_string())
+            ]
+        );
+        // 数字で始まっても英字を含めば英数字の識別子
+        let version: Version = "1.0.0-0alpha".parse().unwrap();
+        assert_eq!(
+            version.pre,
+            vec![Identifier::AlphaNumeric("0alpha".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_pre_release() {
+        for s in [
+            "1.0.0-",
+            "1.0.0-alpha..1",
+            "1.0.0-alpha.",
+            "1.0.0-01",
+            "1.0.0-al_pha",
+            "1.0.0-ä",
+        ] {
+            assert_eq!(s.parse::<Version>(), Err(ParseVersionError), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_parse_build_metadata() {
+        let version: Version = "1.0.0+build.1".parse().unwrap();
+        assert!(version.pre.is_empty());
+        assert_eq!(version.build, vec!["build", "1"]);
+        let version: Version = "1.0.0-alpha+build.123".parse().unwrap();
+        assert_eq!(
+            version.pre,
+            vec![Identifier::AlphaNumeric("alpha".to_string())]
+        );
+        assert_eq!(version.build, vec!["build", "123"]);
+        // ビルドメタデータの先頭の0は許される
+        let version: Version = "1.0.0+001.exp-sha".parse().unwrap();
+        assert_eq!(version.build, vec!["001", "exp-sha"]);
+
+        let version: Version = "1.0.0+build.1-".parse().unwrap();
+        assert_eq!(version.build, vec!["build", "1-"]);
+
+        for s in ["1.0.0+", "1.0.0+build..1", "1.0.0+build+1", "1.0.0+b!"] {
+            assert_eq!(s.parse::<Version>(), Err(ParseVersionError), "{s}");
+        }
+    }
+
+    fn v(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert!(v("1.0.0") < v("2.0.0"));
+        assert!(v("1.1.0") > v("1.0.0"));
+        assert!(v("1.1.1") > v("1.1.0"));
+        // 数値として比較する
+        assert!(v("1.10.0") > v("1.9.0"));
+        assert!(v("2.0.0") > v("1.99.99"));
+        assert_eq!(v("1.2.3"), v("1.2.3"));
+    }
+
+    #[test]
+    fn test_pre_release_precedence() {
+        // SemVer 2.0.0 の仕様の例
+        let chain = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+        ];
+        for pair in chain.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+        assert!(v("1.0.0-rc.1") < v("1.0.1-alpha"));
+        assert!(v("1.0.0-2") < v("1.0.0-10"));
+        assert!(v("1.0.0-999") < v("1.0.0-a"));
+        // 英数字の識別子はASCIIの順
+        assert!(v("1.0.0-Beta") < v("1.0.0-alpha"));
+    }
+
+    #[test]
+    fn test_build_metadata_is_ignored() {
+        assert_eq!(v("1.0.0+build.1").cmp(&v("1.0.0+build.2")), Ordering::Equal);
+        assert_eq!(v("1.0.0+build.1"), v("1.0.0+build.2"));
+        assert_eq!(v("1.0.0-alpha+001"), v("1.0.0-alpha"));
+        assert!(v("1.0.0-alpha+zzz") < v("1.0.0-alpha.1+000"));
+    }
+
+    #[test]
+    fn test_sort_releases() {
+        let mut releases: Vec<Version> = [
+            "1.0.0",
+            "0.9.0",
+            "1.0.0-rc.1",
+            "1.0.0-beta.11",
+            "1.0.0-beta.2",
+            "0.10.0",
+            "1.0.0-alpha",
+        ]
+        .iter()
+        .map(|s| v(s))
+        .collect();
+        releases.sort();
+        let sorted: Vec<String> = releases.iter().map(Version::to_string).collect();
+        assert_eq!(
+            sorted,
+            [
+                "0.9.0",
+                "0.10.0",
+                "1.0.0-alpha",
+                "1.0.0-beta.2",
+                "1.0.0-beta.11",
+                "1.0.0-rc.1",
+                "1.0.0"
+            ]
+        );
+        assert_eq!(releases.iter().max(), Some(&v("1.0.0")));
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for s in [
+            "1.2.3",
+            "0.0.0",
+            "1.0.0-alpha.1",
+            "1.0.0-0.3.7",
+            "1.0.0-x.7.z.92",
+            "1.0.0+build.1",
+            "1.0.0-alpha+001",
+            "1.0.0-beta+exp.sha.5114f85",
+            "1.0.0+21AF26D3----117B344092BD",
+        ] {
+            assert_eq!(s.parse::<Version>().unwrap().to_string(), s);
+        }
     }
 }
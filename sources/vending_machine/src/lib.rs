@@ -3,11 +3,60 @@
 use anyhow::{Result, anyhow};
 
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 /// Represents a vending machine that dispenses drinks and handles money deposits.
 pub struct VendingMachine {
     deposits: HashMap<Money, u32>,
     buttons: Buttons,
+    clock: Box<dyn Clock>,
+    session_timeout: Option<SessionTimeout>,
+    last_activity: Option<Instant>,
+    refunds: HashMap<Money, u32>,
+    forgotten_money: HashMap<Money, u32>,
+    audit_log: Vec<AuditEvent>,
+}
+
+/// A source of the current time, injectable so that session timeouts can be tested.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// A `Clock` backed by the system's monotonic clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// What to do with the deposited money when a session is abandoned.
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy)]
+pub enum TimeoutAction {
+    /// Return the money to the customer.
+    Refund,
+    /// Keep the money in the forgotten money ledger.
+    Forfeit,
+}
+
+/// Configuration of the session inactivity timeout.
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy)]
+pub struct SessionTimeout {
+    pub duration: Duration,
+    pub action: TimeoutAction,
+}
+
+/// An event recorded by the vending machine for auditing.
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy)]
+pub enum AuditEvent {
+    /// A session was abandoned and its deposits were handled by `action`.
+    SessionTimedOut {
+        at: Instant,
+        amount: u32,
+        action: TimeoutAction,
+    },
 }
 
 #[derive(Eq, Hash, PartialEq, Debug, Clone, Copy)]
@@ -47,7 +96,7 @@ pub enum Drink {
 }
 
 /// Represents the different denominations of money that can be used in the vending machine.
-#[derive(Eq, Hash, PartialEq, Debug)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy)]
 pub enum Money {
     One,
     Five,
@@ -58,6 +107,23 @@ pub enum Money {
     OneThousand,
     TenThousand,
 }
+
+impl Money {
+    /// Returns the value of the denomination in yen.
+    pub fn value(&self) -> u32 {
+        match self {
+            Money::One => 1,
+            Money::Five => 5,
+            Money::Ten => 10,
+            Money::Fifty => 50,
+            Money::OneHundred => 100,
+            Money::FiveHundred => 500,
+            Money::OneThousand => 1000,
+            Money::TenThousand => 10000,
+        }
+    }
+}
+
 impl Default for VendingMachine {
     fn default() -> Self {
         Self::new()
@@ -70,6 +136,26 @@ impl VendingMachine {
         Self {
             deposits: HashMap::new(),
             buttons: Buttons::new(),
+            clock: Box::new(SystemClock),
+            session_timeout: None,
+            last_activity: None,
+            refunds: HashMap::new(),
+            forgotten_money: HashMap::new(),
+            audit_log: Vec::new(),
+        }
+    }
+
+    /// Creates a new `VendingMachine` that ends abandoned sessions after `timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The `Clock` used to measure inactivity.
+    /// * `timeout` - How long a session may be idle and what to do with its deposits.
+    pub fn with_session_timeout(clock: Box<dyn Clock>, timeout: SessionTimeout) -> Self {
+        Self {
+            clock,
+            session_timeout: Some(timeout),
+            ..Self::new()
         }
     }
 
@@ -85,6 +171,7 @@ impl VendingMachine {
     /// Returns `Ok(())` if the deposit is successful, or an `Err` with an `anyhow` error
     /// if the money cannot be deposited (e.g., not `Money::OneHundred`).
     pub fn deposit(&mut self, money: Money) -> Result<()> {
+        self.check_session_timeout();
         if money != Money::OneHundred {
             Err(anyhow!("Could not deposit"))
         } else {
@@ -93,6 +180,7 @@ impl VendingMachine {
                 .and_modify(|v| *v += 1)
                 .or_insert(1);
             self.update_button_status();
+            self.last_activity = Some(self.clock.now());
             Ok(())
         }
     }
@@ -167,12 +255,16 @@ impl VendingMachine {
     }
 
     pub fn press_button(&mut self, drink: Drink) -> Result<String> {
+        self.check_session_timeout();
         let result = match drink {
             Drink::Coke => self.get_cola(),
             Drink::OolongTea => self.get_oolong_tea(),
             Drink::Redbull => self.get_redbull(),
         };
         self.update_button_status();
+        if result.is_ok() {
+            self.last_activity = Some(self.clock.now());
+        }
         result
     }
 
@@ -180,6 +272,71 @@ impl VendingMachine {
         self.buttons
     }
 
+    /// Returns the total amount of money currently deposited, in yen.
+    pub fn get_balance(&self) -> u32 {
+        Self::total(&self.deposits)
+    }
+
+    /// Returns the total amount of money refunded by session timeouts, in yen.
+    pub fn get_refunded_amount(&self) -> u32 {
+        Self::total(&self.refunds)
+    }
+
+    /// Returns the total amount of money kept in the forgotten money ledger, in yen.
+    pub fn get_forgotten_money(&self) -> u32 {
+        Self::total(&self.forgotten_money)
+    }
+
+    /// Returns the events recorded for auditing, oldest first.
+    pub fn get_audit_log(&self) -> &[AuditEvent] {
+        &self.audit_log
+    }
+
+    /// Ends the current session if it has been idle for longer than the configured timeout.
+    /// The deposits are refunded or moved to the forgotten money ledger depending on the
+    /// configured `TimeoutAction`, and the event is recorded in the audit log.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the session timed out, or `false` otherwise.
+    pub fn check_session_timeout(&mut self) -> bool {
+        let (Some(timeout), Some(last_activity)) = (self.session_timeout, self.last_activity)
+        else {
+            return false;
+        };
+        let now = self.clock.now();
+        if now.duration_since(last_activity) < timeout.duration {
+            return false;
+        }
+
+        self.last_activity = None;
+        let amount = self.get_balance();
+        if amount == 0 {
+            return false;
+        }
+
+        let ledger = match timeout.action {
+            TimeoutAction::Refund => &mut self.refunds,
+            TimeoutAction::Forfeit => &mut self.forgotten_money,
+        };
+        for (money, count) in self.deposits.drain() {
+            *ledger.entry(money).or_insert(0) += count;
+        }
+        self.audit_log.push(AuditEvent::SessionTimedOut {
+            at: now,
+            amount,
+            action: timeout.action,
+        });
+        self.update_button_status();
+        true
+    }
+
+    fn total(coins: &HashMap<Money, u32>) -> u32 {
+        coins
+            .iter()
+            .map(|(money, count)| money.value() * count)
+            .sum()
+    }
 
     fn update_button_status(&mut self) {
         let status = if let Some(one_hundred) = self.deposits.get(&Money::OneHundred) {
@@ -210,6 +367,42 @@ impl VendingMachine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// A `Clock` whose time only moves when the test advances it.
+    #[derive(Clone)]
+    struct FakeClock {
+        now: Rc<Cell<Instant>>,
+    }
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self {
+                now: Rc::new(Cell::new(Instant::now())),
+            }
+        }
+
+        fn advance(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+    }
+
+    fn timeout_machine(action: TimeoutAction) -> (VendingMachine, FakeClock) {
+        let clock = FakeClock::new();
+        let timeout = SessionTimeout {
+            duration: Duration::from_secs(60),
+            action,
+        };
+        let vm = VendingMachine::with_session_timeout(Box::new(clock.clone()), timeout);
+        (vm, clock)
+    }
 
     /// Test that depositing money updates the deposits.
     /// Only Money::OneHundred should be successfully deposited.
@@ -365,4 +558,94 @@ mod tests {
         };
         assert_eq!(vm.get_button_status(), expected);
     }
+
+    /// Test that an abandoned session is refunded after the timeout.
+    #[test]
+    fn should_refund_deposits_when_session_times_out() {
+        let (mut vm, clock) = timeout_machine(TimeoutAction::Refund);
+        vm.deposit(Money::OneHundred).unwrap();
+        vm.deposit(Money::OneHundred).unwrap();
+
+        clock.advance(Duration::from_secs(59));
+        assert!(!vm.check_session_timeout());
+        assert_eq!(vm.get_balance(), 200);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(vm.check_session_timeout());
+        assert_eq!(vm.get_balance(), 0);
+        assert_eq!(vm.get_refunded_amount(), 200);
+        assert_eq!(vm.get_forgotten_money(), 0);
+        assert_eq!(vm.get_button_status(), Buttons::new());
+        assert_eq!(
+            vm.get_audit_log(),
+            &[AuditEvent::SessionTimedOut {
+                at: clock.now(),
+                amount: 200,
+                action: TimeoutAction::Refund,
+            }]
+        );
+    }
+
+    /// Test that an abandoned session is moved to the forgotten money ledger after the timeout.
+    #[test]
+    fn should_keep_forgotten_money_when_session_times_out() {
+        let (mut vm, clock) = timeout_machine(TimeoutAction::Forfeit);
+        vm.deposit(Money::OneHundred).unwrap();
+
+        clock.advance(Duration::from_secs(60));
+        assert!(vm.check_session_timeout());
+        assert_eq!(vm.get_balance(), 0);
+        assert_eq!(vm.get_refunded_amount(), 0);
+        assert_eq!(vm.get_forgotten_money(), 100);
+        assert_eq!(vm.get_audit_log().len(), 1);
+    }
+
+    /// Test that activity restarts the timeout and that a stale balance cannot be spent.
+    #[test]
+    fn should_restart_timeout_on_activity_and_not_dispense_after_timeout() {
+        let (mut vm, clock) = timeout_machine(TimeoutAction::Refund);
+        vm.deposit(Money::OneHundred).unwrap();
+        clock.advance(Duration::from_secs(40));
+        vm.deposit(Money::OneHundred).unwrap();
+        clock.advance(Duration::from_secs(40));
+        assert!(!vm.check_session_timeout());
+
+        clock.advance(Duration::from_secs(20));
+        assert!(vm.press_button(Drink::Coke).is_err());
+        assert_eq!(vm.get_refunded_amount(), 200);
+    }
+
+    /// Test that pressing a button that cannot dispense does not keep the session alive.
+    #[test]
+    fn should_not_restart_timeout_on_failed_purchase() {
+        let (mut vm, clock) = timeout_machine(TimeoutAction::Refund);
+        vm.deposit(Money::OneHundred).unwrap();
+        clock.advance(Duration::from_secs(40));
+        assert!(vm.press_button(Drink::Redbull).is_err());
+
+        clock.advance(Duration::from_secs(20));
+        assert!(vm.check_session_timeout());
+        assert_eq!(vm.get_refunded_amount(), 100);
+    }
+
+    /// Test that nothing is recorded when an idle session has no money left.
+    #[test]
+    fn should_not_record_timeout_when_no_money_deposited() {
+        let (mut vm, clock) = timeout_machine(TimeoutAction::Refund);
+        vm.deposit(Money::OneHundred).unwrap();
+        vm.press_button(Drink::Coke).unwrap();
+
+        clock.advance(Duration::from_secs(120));
+        assert!(!vm.check_session_timeout());
+        assert!(vm.get_audit_log().is_empty());
+    }
+
+    /// Test that a machine without a configured timeout keeps the deposits.
+    #[test]
+    fn should_keep_deposits_without_session_timeout() {
+        let mut vm = VendingMachine::new();
+        vm.deposit(Money::OneHundred).unwrap();
+        assert!(!vm.check_session_timeout());
+        assert_eq!(vm.get_balance(), 100);
+    }
 }